@@ -0,0 +1,99 @@
+use pest::error::LineColLocation;
+use pest_consume::{match_nodes, Error, Parser};
+
+type Result<T> = std::result::Result<T, Error<Rule>>;
+type Node<'i> = pest_consume::Node<'i, Rule, ()>;
+
+#[derive(Parser)]
+#[grammar = "../examples/csv/csv.pest"]
+struct CSVParser;
+
+#[pest_consume::parser]
+impl CSVParser {
+    fn EOI(_input: Node) -> Result<()> {
+        Ok(())
+    }
+
+    fn number(input: Node) -> Result<f64> {
+        match input.as_str().parse::<f64>() {
+            Ok(x) => Ok(x),
+            Err(e) => {
+                // Only returns an error if errors are not being collected.
+                input.report_error(input.error(e))?;
+                Ok(f64::NAN)
+            }
+        }
+    }
+
+    fn string(input: Node) -> Result<f64> {
+        // Returning an error always stops parsing.
+        Err(input.error("strings are not supported"))
+    }
+
+    fn field(input: Node) -> Result<f64> {
+        Ok(match_nodes!(input.into_children();
+            [number(n)] => n,
+            [string(s)] => s,
+        ))
+    }
+
+    fn record(input: Node) -> Result<Vec<f64>> {
+        Ok(match_nodes!(input.into_children();
+            [field(fields)..] => fields.collect(),
+        ))
+    }
+
+    fn file(input: Node) -> Result<Vec<Vec<f64>>> {
+        Ok(match_nodes!(input.into_children();
+            [record(records).., EOI(_)] => records.collect(),
+        ))
+    }
+}
+
+fn parse_csv(input_str: &str) -> Result<Vec<Vec<f64>>> {
+    let input = CSVParser::parse(Rule::file, input_str)?.single()?;
+    CSVParser::file(input)
+}
+
+fn parse_csv_collecting_errors(
+    input_str: &str,
+) -> (Option<Vec<Vec<f64>>>, Vec<Error<Rule>>) {
+    CSVParser::parse_collecting_errors(Rule::file, input_str, CSVParser::file)
+}
+
+fn main() -> Result<()> {
+    assert_eq!(parse_csv("1, 2\n3, 4")?, vec![vec![1., 2.], vec![3., 4.]]);
+
+    // When parsing normally, a reported error is returned and stops parsing.
+    let error = parse_csv("1, 2.2.2\n3, 4.4.4").unwrap_err();
+    assert_eq!(error.variant.message(), "invalid float literal");
+    assert_eq!(error.line_col, LineColLocation::Span((1, 4), (1, 9)));
+
+    // When collecting errors, all of them are recorded and parsing carries on.
+    let (file, errors) = parse_csv_collecting_errors("1, 2.2.2\n3, 4.4.4");
+    let file = file.unwrap();
+    assert_eq!(file.len(), 2);
+    assert!(file[0][1].is_nan() && file[1][1].is_nan());
+    let lines: Vec<_> = errors.iter().map(|e| e.line_col.clone()).collect();
+    assert_eq!(
+        lines,
+        vec![
+            LineColLocation::Span((1, 4), (1, 9)),
+            LineColLocation::Span((2, 4), (2, 9)),
+        ]
+    );
+
+    // An error that stops parsing comes after the ones reported before it.
+    let (file, errors) = parse_csv_collecting_errors("1.1.1, 2\n'x', 4.4.4");
+    assert!(file.is_none());
+    let messages: Vec<_> = errors
+        .iter()
+        .map(|e| e.variant.message().into_owned())
+        .collect();
+    assert_eq!(
+        messages,
+        vec!["invalid float literal", "strings are not supported"]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use std::convert::TryFrom;
+
+use pest_consume::{Error, Parser};
+
+type Result<T> = std::result::Result<T, Error<Rule>>;
+
+#[derive(Parser)]
+#[grammar = "../examples/cst/grammar.pest"]
+struct DeclParser;
+
+// The tree is walked through the `cst` types, so no consumer method is needed.
+#[pest_consume::parser]
+impl DeclParser {}
+
+mod cst {
+    use super::Rule;
+    pest_consume::cst!(grammar = "../examples/cst/grammar.pest");
+}
+
+/// Types generated from a grammar that doesn't match the parser.
+mod stale_cst {
+    use super::Rule;
+    pest_consume::cst!(grammar = "../examples/cst/stale.pest");
+}
+
+fn parse_file(input_str: &str) -> Result<cst::File<'_>> {
+    let node = DeclParser::parse(Rule::file, input_str)?.single()?;
+    cst::File::try_from(node)
+}
+
+fn main() -> Result<()> {
+    let file = parse_file("x = 1 apples; y; a..z: 3; [1 b, 2 'c', 3 d]; [];")?;
+
+    // `decl` has exactly one `ident`, and maybe a `value`.
+    let decls = file.decl();
+    assert_eq!(decls.len(), 2);
+    assert_eq!(decls[0].ident()?.as_str(), "x");
+    let value = decls[0].value().unwrap();
+    assert_eq!(value.as_str(), "1 apples");
+    assert_eq!(decls[1].ident()?.as_str(), "y");
+    assert!(decls[1].value().is_none());
+
+    // `value` always has a `number`, and either an `ident` or a `quoted`.
+    assert_eq!(value.number()?.as_str(), "1");
+    assert_eq!(value.ident().unwrap().as_str(), "apples");
+    assert!(value.quoted().is_none());
+
+    // `range` has two `ident`s.
+    let ranges = file.range();
+    assert_eq!(ranges.len(), 1);
+    let idents: Vec<_> = ranges[0].ident().iter().map(|i| i.as_str()).collect();
+    assert_eq!(idents, vec!["a", "z"]);
+    assert_eq!(ranges[0].number()?.as_str(), "3");
+
+    // `list` has any number of `value`s, through the recursive `items`.
+    let lists = file.list();
+    assert_eq!(lists.len(), 2);
+    let values = lists[0].value();
+    assert_eq!(values.len(), 3);
+    let quoted = values[1].quoted().unwrap();
+    assert!(values[1].ident().is_none());
+    // An atomic rule has no children, only its text.
+    assert_eq!(quoted.as_str(), "'c'");
+    assert_eq!(quoted.node().children().count(), 0);
+    assert!(lists[1].value().is_empty());
+
+    // The rule of the node is checked.
+    let node = DeclParser::parse(Rule::decl, "x")?.single()?;
+    let error = cst::File::<()>::try_from(node).unwrap_err();
+    assert!(error.to_string().contains("expected `file`, found `decl`"));
+
+    // A child that the grammar of the types requires, but the node doesn't have.
+    let node = DeclParser::parse(Rule::decl, "y")?.single()?;
+    let decl = stale_cst::Decl::<()>::try_from(node)?;
+    assert_eq!(decl.ident()?.as_str(), "y");
+    let error = decl.value().unwrap_err();
+    assert_eq!(error.variant.message(), "expected a `value` child");
+    assert_eq!(error.location, pest::error::InputLocation::Span((0, 1)));
+
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use pest_consume::{match_nodes, Error, Parser};
 
 #[derive(Debug)]
+#[allow(dead_code)]
 enum CSVField<'a> {
     Number(f64),
     String(&'a str),
@@ -15,7 +16,7 @@ type Node<'i> = pest_consume::Node<'i, Rule, ()>;
 #[grammar = "../examples/csv/csv.pest"]
 pub struct CSVParser;
 
-#[pest_consume::parser]
+#[pest_consume::parser(grammar = "../examples/csv/csv.pest", exhaustive)]
 impl CSVParser {
     fn EOI(_input: Node) -> Result<()> {
         Ok(())
@@ -29,7 +30,7 @@ impl CSVParser {
             .map_err(|e| input.error(e))
     }
 
-    fn string(input: Node) -> Result<&str> {
+    fn string(input: Node<'_>) -> Result<&str> {
         Ok(input.as_str())
     }
 
@@ -53,7 +54,7 @@ impl CSVParser {
     }
 }
 
-fn parse_csv(input_str: &str) -> Result<CSVFile> {
+fn parse_csv(input_str: &str) -> Result<CSVFile<'_>> {
     // Parse the input into `Nodes`
     let inputs = CSVParser::parse(Rule::file, input_str)?;
     // There should be a single root node in the parsed tree
@@ -62,6 +63,11 @@ fn parse_csv(input_str: &str) -> Result<CSVFile> {
     CSVParser::file(input)
 }
 
+// The contents of a file are dropped once it's parsed, so we don't borrow from them.
+fn count_records(input: Node) -> Result<usize> {
+    Ok(CSVParser::file(input)?.len())
+}
+
 fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let parsed = parse_csv("-20, 12.5\n42, 0")?;
     let mut sum = 0.;
@@ -80,5 +86,23 @@ fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let successful_parse = parse_csv("-273.15 , ' a string '\n\n42, 0")?;
     println!("success: {:?}", successful_parse);
 
+    // Errors mention the name of the input.
+    let error =
+        CSVParser::parse_named(Rule::file, "data.csv", "1, 2\n3, 4.4.4")
+            .and_then(|inputs| inputs.single())
+            .and_then(CSVParser::file)
+            .unwrap_err();
+    assert!(error.to_string().contains("--> data.csv:2:4"));
+
+    let path = std::env::temp_dir().join("pest_consume_example.csv");
+    std::fs::write(&path, "1, 2\n3, 4")?;
+    assert_eq!(CSVParser::parse_file(Rule::file, &path, count_records)??, 2);
+    std::fs::write(&path, "1, 2\n3, 4.4.4")?;
+    let error =
+        CSVParser::parse_file(Rule::file, &path, count_records)?.unwrap_err();
+    let location = format!("--> {}:2:4", path.display());
+    assert!(error.to_string().contains(&location));
+    std::fs::remove_file(&path)?;
+
     Ok(())
 }
@@ -0,0 +1,97 @@
+use pest_consume::{match_nodes, Parser};
+
+/// The error type of the application, which the whole parser uses.
+#[derive(Debug)]
+enum ConfigError {
+    Syntax(Box<pest_consume::Error<Rule>>),
+    Invalid { offset: usize, message: String },
+}
+
+impl pest_consume::ParseError<Rule> for ConfigError {
+    fn from_pest_error(error: pest_consume::Error<Rule>) -> Self {
+        ConfigError::Syntax(Box::new(error))
+    }
+    fn from_span(span: pest::Span<'_>, message: String) -> Self {
+        ConfigError::Invalid {
+            offset: span.start(),
+            message,
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, ConfigError>;
+type Node<'i> = pest_consume::Node<'i, Rule, (), ConfigError>;
+
+#[derive(Parser)]
+#[grammar = "../examples/custom_errors/grammar.pest"]
+struct ConfigParser;
+
+#[pest_consume::parser(error = ConfigError)]
+impl ConfigParser {
+    fn key(input: Node) -> Result<String> {
+        Ok(input.as_str().to_owned())
+    }
+
+    fn value(input: Node) -> Result<u16> {
+        input.as_str().parse().map_err(|e| input.error(e))
+    }
+
+    fn entry(input: Node) -> Result<(String, u16)> {
+        Ok(match_nodes!(input.into_children();
+            [key(k), value(v)] => (k, v),
+        ))
+    }
+
+    fn file(input: Node) -> Result<Vec<(String, u16)>> {
+        Ok(match_nodes!(input.into_children();
+            [entry(es).., EOI(_)] => es.collect(),
+        ))
+    }
+}
+
+fn parse_config(input_str: &str) -> Result<Vec<(String, u16)>> {
+    let inputs = ConfigParser::parse(Rule::file, input_str)?;
+    ConfigParser::file(inputs.single()?)
+}
+
+fn main() -> Result<()> {
+    assert_eq!(
+        parse_config("width = 80, height = 24")?,
+        vec![("width".to_owned(), 80), ("height".to_owned(), 24)]
+    );
+
+    // Errors from `Node::error`.
+    match parse_config("width = 80, height = 99999") {
+        Err(ConfigError::Invalid { offset, message }) => {
+            assert_eq!(offset, 21);
+            assert_eq!(message, "number too large to fit in target type");
+        }
+        r => panic!("expected an invalid value, found {:?}", r),
+    }
+
+    // Errors from `match_nodes!`.
+    match parse_config("width = wide") {
+        Err(ConfigError::Invalid { offset, message }) => {
+            assert_eq!(offset, 8);
+            assert_eq!(message, "expected `[key, value]`, found `[key, key]`");
+        }
+        r => panic!("expected a mismatch, found {:?}", r),
+    }
+
+    // Errors from `Nodes::single`.
+    let entry = ConfigParser::parse(Rule::entry, "width = 80")?.single()?;
+    match entry.into_children().single() {
+        Err(ConfigError::Invalid { offset, .. }) => assert_eq!(offset, 0),
+        r => panic!("expected several nodes, found {:?}", r),
+    }
+
+    // Errors from pest.
+    match parse_config("width = ") {
+        Err(ConfigError::Syntax(e)) => {
+            assert_eq!(e.line_col, pest::error::LineColLocation::Pos((1, 9)))
+        }
+        r => panic!("expected a syntax error, found {:?}", r),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use pest_consume::{Diagnostic, Error, Parser};
+
+type Result<T> = std::result::Result<T, Error<Rule>>;
+type Node<'i> = pest_consume::Node<'i, Rule, ()>;
+
+#[derive(Parser)]
+#[grammar = "../examples/diagnostics/grammar.pest"]
+struct FieldsParser;
+
+#[pest_consume::parser]
+impl FieldsParser {}
+
+/// The `(name, value)` nodes of each field of the input.
+fn parse_fields(input_str: &str) -> Result<Vec<(Node<'_>, Node<'_>)>> {
+    let file = FieldsParser::parse(Rule::file, input_str)?.single()?;
+    Ok(file
+        .into_children()
+        .filter(|node| node.as_rule() == Rule::field)
+        .map(|field| {
+            let mut children = field.into_children();
+            (children.next().unwrap(), children.next().unwrap())
+        })
+        .collect())
+}
+
+/// Reports the first field that is defined twice, pointing at both definitions.
+fn check_duplicates<'i>(
+    fields: &[(Node<'i>, Node<'i>)],
+) -> Option<Diagnostic<'i>> {
+    for (i, (name, _)) in fields.iter().enumerate() {
+        let previous = fields[..i]
+            .iter()
+            .find(|(other, _)| other.as_str() == name.as_str());
+        if let Some((previous, _)) = previous {
+            return Some(
+                name.diagnostic(format!("duplicate field `{}`", name.as_str()))
+                    .with_label("defined again here")
+                    .with_secondary_label(
+                        previous.as_span(),
+                        "first defined here",
+                    )
+                    .with_help("remove one of the definitions"),
+            );
+        }
+    }
+    None
+}
+
+fn main() -> Result<()> {
+    // A single label.
+    let input = "x = 1\nlength = 12345";
+    let fields = parse_fields(input)?;
+    let (_, value) = &fields[1];
+    let diagnostic = value
+        .diagnostic("number too large")
+        .with_label("must be at most 255");
+    assert_eq!(
+        diagnostic.to_string(),
+        "\
+error: number too large
+ --> 2:10
+  |
+2 | length = 12345
+  |          ^^^^^ must be at most 255"
+    );
+
+    // Two labels on separate lines, with the line between them.
+    let input = "x = 1\ny = 2\nx = 3";
+    let diagnostic = check_duplicates(&parse_fields(input)?).unwrap();
+    assert_eq!(
+        diagnostic.to_string(),
+        "\
+error: duplicate field `x`
+ --> 3:1
+  |
+1 | x = 1
+  | - first defined here
+2 | y = 2
+3 | x = 3
+  | ^ defined again here
+  |
+  = help: remove one of the definitions"
+    );
+
+    // A span over several lines is underlined on each of them.
+    let input = "list = [1,\n\n  2]";
+    let fields = parse_fields(input)?;
+    let (name, value) = &fields[0];
+    let diagnostic = value
+        .diagnostic("lists are not supported")
+        .with_label("this list")
+        .with_secondary_label(name.as_span(), "in this field");
+    assert_eq!(
+        diagnostic.to_string(),
+        "\
+error: lists are not supported
+ --> 1:8
+  |
+1 | list = [1,
+  |        ^^^
+  | ---- in this field
+2 |
+3 |   2]
+  |   ^^ this list"
+    );
+
+    // Notes and help messages, with the name of the input.
+    let input = "\n\n\n\n\n\n\n\n\nvolume = 11";
+    let fields = parse_fields(input)?;
+    let (name, _) = &fields[0];
+    let diagnostic = name
+        .diagnostic("unknown field `volume`")
+        .with_note("fields are case-sensitive")
+        .with_note("see the list of fields in the manual")
+        .with_help("did you mean `Volume`?")
+        .with_source_name("settings.txt");
+    assert_eq!(
+        diagnostic.to_string(),
+        "\
+error: unknown field `volume`
+  --> settings.txt:10:1
+   |
+10 | volume = 11
+   | ^^^^^^
+   |
+   = note: fields are case-sensitive
+   = note: see the list of fields in the manual
+   = help: did you mean `Volume`?"
+    );
+
+    // Errors keep the primary span, and the rest of the diagnostic in their message.
+    let error: Error<Rule> = diagnostic.into();
+    assert_eq!(
+        error.variant.message(),
+        "unknown field `volume`\n\
+         note: fields are case-sensitive\n\
+         note: see the list of fields in the manual\n\
+         help: did you mean `Volume`?"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use pest_consume::{match_nodes, Error, Parser};
+
+type Result<T> = std::result::Result<T, Error<Rule>>;
+type Node<'i> = pest_consume::Node<'i, Rule, ()>;
+
+#[derive(Parser)]
+#[grammar = "../examples/custom_errors/grammar.pest"]
+struct ConfigParser;
+
+#[pest_consume::parser]
+impl ConfigParser {
+    #[display_name = "the end of the input"]
+    fn EOI(_input: Node) -> Result<()> {
+        Ok(())
+    }
+
+    #[display_name = "a key"]
+    fn key(input: Node) -> Result<String> {
+        Ok(input.as_str().to_owned())
+    }
+
+    #[display_name = "a value"]
+    fn value(input: Node) -> Result<u16> {
+        input.as_str().parse().map_err(|e| input.error(e))
+    }
+
+    #[alias(setting)]
+    #[display_name = "an entry"]
+    fn entry(input: Node) -> Result<(String, u16)> {
+        Ok(match_nodes!(input.into_children();
+            [key(k), value(v)] => (k, v),
+        ))
+    }
+
+    fn file(input: Node) -> Result<Vec<(String, u16)>> {
+        Ok(match_nodes!(input.into_children();
+            [setting(es).., EOI(_)] => es.collect(),
+        ))
+    }
+}
+
+fn parse_config(input_str: &str) -> Result<Vec<(String, u16)>> {
+    let inputs = ConfigParser::parse(Rule::file, input_str)?;
+    ConfigParser::file(inputs.single()?)
+}
+
+/// The children of the first entry of the input.
+fn first_entry(input_str: &str) -> Result<Vec<Node<'_>>> {
+    let entry = ConfigParser::parse(Rule::entry, input_str)?.single()?;
+    Ok(entry.into_children().collect())
+}
+
+/// A parser where the `value` rule has no method of its own, and values are parsed as keys.
+mod keys_only {
+    use super::{Node, Result, Rule};
+
+    struct KeysParser;
+
+    #[pest_consume::parser(
+        parser = super::ConfigParser,
+        grammar = "../examples/custom_errors/grammar.pest"
+    )]
+    impl KeysParser {
+        #[alias(value)]
+        fn key(input: Node) -> Result<String> {
+            Ok(input.as_str().to_owned())
+        }
+    }
+
+    pub(super) fn parse_value(input: Node) -> Result<String> {
+        KeysParser::value(input)
+    }
+}
+
+fn main() -> Result<()> {
+    assert_eq!(
+        parse_config("width = 80, height = 24")?,
+        vec![("width".to_owned(), 80), ("height".to_owned(), 24)]
+    );
+    assert_eq!(ConfigParser::rule_display_name(Rule::key), Some("a key"));
+    assert_eq!(ConfigParser::rule_display_name(Rule::file), None);
+
+    // Errors from pest.
+    let error = parse_config("width = ").unwrap_err();
+    assert_eq!(error.variant.message(), "expected a key or a value");
+    let error = parse_config("width = 80 height").unwrap_err();
+    assert_eq!(error.variant.message(), "expected the end of the input");
+
+    // Errors from `match_nodes!`.
+    let error = parse_config("width = wide").unwrap_err();
+    assert_eq!(
+        error.variant.message(),
+        "expected `[a key, a value]`, found `[a key, a key]`"
+    );
+
+    // Errors from `Nodes::single`.
+    let entry = ConfigParser::parse(Rule::entry, "width = 80")?.single()?;
+    let error = entry.into_children().single().unwrap_err();
+    assert_eq!(
+        error.variant.message(),
+        "Expected a single node, instead got: [a key, a value]"
+    );
+
+    // Errors from calling a method on a node of another rule.
+    let error =
+        ConfigParser::value(first_entry("width = 80")?.remove(0)).unwrap_err();
+    assert_eq!(
+        error.variant.message(),
+        "pest_consume::parser: called the `value` method on a node with rule `a key`"
+    );
+    let error = ConfigParser::setting(first_entry("width = 80")?.remove(0))
+        .unwrap_err();
+    assert_eq!(
+        error.variant.message(),
+        "pest_consume::parser: called method `setting` on a node with rule `a key`"
+    );
+
+    // Errors from calling an alias that is also a rule, on a node of that rule.
+    let mut nodes = first_entry("width = 80")?;
+    let key = nodes.remove(0);
+    assert_eq!(keys_only::parse_value(key)?, "width");
+    let error = keys_only::parse_value(nodes.remove(0)).unwrap_err();
+    assert_eq!(
+        error.variant.message(),
+        "pest_consume::parser: missing method for rule `a value`"
+    );
+
+    Ok(())
+}
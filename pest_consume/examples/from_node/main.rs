@@ -0,0 +1,207 @@
+use pest::Span;
+use pest_consume::{match_nodes, Error, FromNode, Parser};
+
+type Result<T> = std::result::Result<T, Error<Rule>>;
+type Node<'i> = pest_consume::Node<'i, Rule, ()>;
+
+#[derive(Parser)]
+#[grammar = "../examples/from_node/grammar.pest"]
+struct StatementParser;
+
+/// A number, along with where it appears in the input.
+#[derive(FromNode, Debug, PartialEq)]
+#[rule(number)]
+struct Number<'i> {
+    #[from_str]
+    value: u32,
+    span: Span<'i>,
+}
+
+/// An enum with a rule: the variant is chosen from the only child of an `expr` node.
+#[derive(FromNode, Debug, PartialEq)]
+#[rule(expr)]
+enum Expr<'i> {
+    #[rule(number)]
+    Number(#[from_str] u32),
+    #[rule(ident)]
+    Ident(&'i str),
+    Paren(Box<Paren<'i>>),
+}
+
+#[derive(FromNode, Debug, PartialEq)]
+#[rule(paren)]
+struct Paren<'i> {
+    inner: Expr<'i>,
+}
+
+/// An enum without a rule: the variant is chosen from the node itself.
+#[derive(FromNode, Debug, PartialEq)]
+enum Atom<'i> {
+    Number(Number<'i>),
+    #[rule(ident)]
+    Ident(&'i str),
+}
+
+#[derive(FromNode, Debug, PartialEq)]
+#[rule(annotation)]
+struct Annotation<'i> {
+    #[rule(ident)]
+    name: &'i str,
+}
+
+#[derive(FromNode, Debug, PartialEq)]
+#[rule(binding)]
+struct Binding<'i> {
+    text: &'i str,
+    #[rule(ident)]
+    name: &'i str,
+    annotation: Option<Annotation<'i>>,
+    value: Expr<'i>,
+}
+
+#[derive(FromNode, Debug, PartialEq)]
+#[rule(tuple)]
+struct Tuple<'i> {
+    atoms: Vec<Atom<'i>>,
+}
+
+#[derive(FromNode, Debug, PartialEq)]
+#[rule(count)]
+struct Count<'i> {
+    #[rule(number)]
+    count: usize,
+    #[rule(ident)]
+    unit: Span<'i>,
+}
+
+#[derive(FromNode, Debug, PartialEq)]
+enum Statement<'i> {
+    Binding(Binding<'i>),
+    Tuple(Tuple<'i>),
+    Count(Count<'i>),
+}
+
+#[derive(FromNode, Debug, PartialEq)]
+#[rule(file)]
+struct File<'i> {
+    statements: Vec<Statement<'i>>,
+}
+
+#[pest_consume::parser]
+impl StatementParser {
+    #[from_node]
+    fn binding(input: Node) -> Result<Binding>;
+    #[from_node]
+    fn file(input: Node) -> Result<File>;
+}
+
+fn parse_file(input_str: &str) -> Result<File<'_>> {
+    let inputs = StatementParser::parse(Rule::file, input_str)?;
+    StatementParser::file(inputs.single()?)
+}
+
+/// A grammar that doesn't use `EOI`, so its `Rule` has no `EOI` variant.
+mod without_eoi {
+    use pest_consume::{Error, FromNode, Parser};
+
+    #[derive(Parser)]
+    #[grammar_inline = r#"
+        WHITESPACE = _{ " " }
+        key = @{ ASCII_ALPHA+ }
+        value = @{ ASCII_DIGIT+ }
+        setting = { key ~ "=" ~ value }
+    "#]
+    struct SettingParser;
+
+    #[pest_consume::parser]
+    impl SettingParser {}
+
+    #[derive(FromNode, Debug, PartialEq)]
+    #[rule(setting)]
+    pub(super) struct Setting<'i> {
+        #[rule(key)]
+        pub(super) key: &'i str,
+        #[rule(value)]
+        pub(super) value: u16,
+    }
+
+    pub(super) fn parse_setting(
+        input_str: &str,
+    ) -> Result<Setting<'_>, Error<Rule>> {
+        let input = SettingParser::parse(Rule::setting, input_str)?.single()?;
+        Setting::from_node(input)
+    }
+}
+
+fn main() -> Result<()> {
+    let input_str = "let x: int = (42)\n[1 y]";
+    let file = parse_file(input_str)?;
+    assert_eq!(file.statements.len(), 2);
+
+    match &file.statements[0] {
+        Statement::Binding(b) => {
+            assert_eq!(b.text, "let x: int = (42)");
+            assert_eq!(b.name, "x");
+            assert_eq!(b.annotation, Some(Annotation { name: "int" }));
+            assert_eq!(
+                b.value,
+                Expr::Paren(Box::new(Paren {
+                    inner: Expr::Number(42)
+                }))
+            );
+        }
+        s => panic!("expected a binding, found {:?}", s),
+    }
+    match &file.statements[1] {
+        Statement::Tuple(t) => {
+            assert_eq!(t.atoms.len(), 2);
+            match &t.atoms[0] {
+                Atom::Number(n) => {
+                    assert_eq!(n.value, 1);
+                    assert_eq!(n.span.start(), 19);
+                    assert_eq!(n.span.as_str(), "1");
+                }
+                a => panic!("expected a number, found {:?}", a),
+            }
+            assert_eq!(t.atoms[1], Atom::Ident("y"));
+        }
+        s => panic!("expected a tuple, found {:?}", s),
+    }
+
+    // A missing optional child.
+    let file = parse_file("let y = z")?;
+    match &file.statements[0] {
+        Statement::Binding(b) => {
+            assert_eq!(b.annotation, None);
+            assert_eq!(b.value, Expr::Ident("z"));
+        }
+        s => panic!("expected a binding, found {:?}", s),
+    }
+
+    // Methods declared with `#[from_node]` can be used in `match_nodes!`.
+    let inputs = StatementParser::parse(Rule::file, "let a = 1 let b = 2")?;
+    let names: Vec<_> = match_nodes!(<StatementParser>;
+        inputs.single()?.into_children();
+        [binding(bs).., EOI(_)] => bs.map(|b| b.name).collect(),
+    );
+    assert_eq!(names, vec!["a", "b"]);
+
+    let file = parse_file("#3 apples")?;
+    match &file.statements[0] {
+        Statement::Count(c) => {
+            assert_eq!(c.count, 3);
+            assert_eq!(c.unit.as_str(), "apples");
+        }
+        s => panic!("expected a count, found {:?}", s),
+    }
+
+    // Errors are reported on the offending node.
+    let error = parse_file("#99999999999999999999999 apples").unwrap_err();
+    assert_eq!(error.location, pest::error::InputLocation::Span((1, 24)));
+    assert_eq!(parse_file("")?.statements, vec![]);
+
+    let setting = without_eoi::parse_setting("width = 80").unwrap();
+    assert_eq!((setting.key, setting.value), ("width", 80));
+
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use pest_consume::{match_nodes, Error, Parser};
+
+type Result<T> = std::result::Result<T, Error<Rule>>;
+type Node<'i, D> = pest_consume::Node<'i, Rule, D>;
+
+/// Where the values of variables come from.
+trait Env {
+    fn lookup(&self, name: &str) -> Option<i64>;
+}
+
+/// No variables.
+impl Env for () {
+    fn lookup(&self, _name: &str) -> Option<i64> {
+        None
+    }
+}
+
+impl Env for &HashMap<String, i64> {
+    fn lookup(&self, name: &str) -> Option<i64> {
+        self.get(name).copied()
+    }
+}
+
+#[derive(Parser)]
+#[grammar = "../examples/generic_data/grammar.pest"]
+struct SumParser;
+
+#[pest_consume::parser]
+impl<D: Env> SumParser {
+    fn number(input: Node<D>) -> Result<i64> {
+        input.as_str().parse().map_err(|e| input.error(e))
+    }
+
+    fn var(input: Node<D>) -> Result<i64> {
+        input
+            .user_data()
+            .lookup(input.as_str())
+            .ok_or_else(|| input.error("unknown variable"))
+    }
+
+    fn sum(input: Node<D>) -> Result<i64> {
+        Ok(match_nodes!(input.into_children();
+            [number(ts) | var(ts)..] => ts.sum(),
+        ))
+    }
+
+    fn file(input: Node<D>) -> Result<i64> {
+        Ok(match_nodes!(input.into_children();
+            [sum(s), EOI(_)] => s,
+        ))
+    }
+}
+
+fn parse_sum<D: Env>(input_str: &str, env: D) -> Result<i64> {
+    let inputs = SumParser::parse_with_userdata(Rule::file, input_str, env)?;
+    SumParser::file(inputs.single()?)
+}
+
+fn main() -> Result<()> {
+    // Without variables.
+    assert_eq!(parse_sum("1 + 2 + 3", ())?, 6);
+    let error = parse_sum("1 + x", ()).unwrap_err();
+    assert!(error.to_string().contains("unknown variable"));
+
+    // With variables, using the same methods.
+    let mut env = HashMap::new();
+    env.insert("x".to_owned(), 10);
+    env.insert("y".to_owned(), 20);
+    assert_eq!(parse_sum("1 + x + y", &env)?, 31);
+    assert!(parse_sum("1 + z", &env).is_err());
+
+    Ok(())
+}
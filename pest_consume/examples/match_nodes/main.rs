@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use pest::error::InputLocation;
+use pest_consume::{match_nodes, Error, Parser};
+
+type Result<T> = std::result::Result<T, Error<Rule>>;
+type Node<'i> = pest_consume::Node<'i, Rule, ()>;
+type Nodes<'i> = pest_consume::Nodes<'i, Rule, ()>;
+type Env<'a> = HashMap<&'a str, u8>;
+
+#[derive(Parser)]
+#[grammar = "../examples/match_nodes/grammar.pest"]
+struct ListParser;
+
+#[pest_consume::parser]
+impl ListParser {
+    #[from_str]
+    fn number(input: Node) -> Result<u8>;
+
+    #[as_str]
+    fn ident(input: Node) -> Result<String>;
+
+    fn string(input: Node) -> Result<String> {
+        let s = input.as_str();
+        Ok(s[1..s.len() - 1].to_owned())
+    }
+
+    /// Looks the variable up in `env`, and adds `offset` to its value.
+    fn var(input: Node, env: &Env, offset: u8) -> Result<u8> {
+        let name = &input.as_str()[1..];
+        match env.get(name) {
+            Some(value) => Ok(value + offset),
+            None => Err(input.error(format!("unbound variable `{}`", name))),
+        }
+    }
+}
+
+/// The children of the `list` node of the input.
+fn items<'i>(input_str: &'i str) -> Result<Nodes<'i>> {
+    let file = ListParser::parse(Rule::file, input_str)?.single()?;
+    // `EOI` uses the method that is generated when there isn't one.
+    Ok(match_nodes!(<ListParser>; file.into_children();
+        [list, EOI(_)] => list.into_children(),
+    ))
+}
+
+fn numbers(input_str: &str) -> Result<Vec<u8>> {
+    Ok(match_nodes!(<ListParser>; items(input_str)?;
+        [number(ns)..] => ns.collect(),
+    ))
+}
+
+fn optional(
+    input_str: &str,
+) -> Result<(Option<u8>, Option<String>, Option<u8>)> {
+    Ok(match_nodes!(<ListParser>; items(input_str)?;
+        [number(a)?, ident(b)?, number(c)?] => (a, b, c),
+    ))
+}
+
+fn numbers_and_idents(input_str: &str) -> Result<(Vec<u8>, Vec<String>)> {
+    Ok(match_nodes!(<ListParser>; items(input_str)?;
+        [number(ns).., ident(is)..] => (ns.collect(), is.collect()),
+    ))
+}
+
+fn around_ident(input_str: &str) -> Result<(Vec<u8>, String, Vec<u8>)> {
+    Ok(match_nodes!(<ListParser>; items(input_str)?;
+        [number(before).., ident(i), number(after)..] => {
+            (before.collect(), i, after.collect())
+        }
+    ))
+}
+
+fn last_ident(input_str: &str) -> Result<(usize, String)> {
+    Ok(match_nodes!(<ListParser>; items(input_str)?;
+        [others.., ident(last)] => (others.count(), last),
+    ))
+}
+
+fn classify(input_str: &str) -> Result<String> {
+    Ok(match_nodes!(<ListParser>; items(input_str)?;
+        // `where` guards see the raw nodes, so a rejected branch doesn't parse anything.
+        [number(n)] where n.as_str().len() <= 2 => format!("small {}", n),
+        [number(ns)..] where ns.len() > 2 => "many numbers".to_owned(),
+        [n] where n.as_str().len() > 4 => "too long".to_owned(),
+        // `if` guards see the parsed values.
+        [number(n)] if n >= 100 => format!("big {}", n),
+        [ident(i)] if i == "self" => "self".to_owned(),
+    ))
+}
+
+fn words(input_str: &str) -> Result<(Option<u8>, Vec<String>)> {
+    Ok(match_nodes!(<ListParser>; items(input_str)?;
+        // Each node is parsed with the method of its own rule.
+        [number(n)?, ident(ws) | string(ws)..] => (n, ws.collect()),
+    ))
+}
+
+/// Sums the first `count` numbers.
+fn sum_first(input_str: &str, count: usize) -> Result<(u32, Option<String>)> {
+    Ok(match_nodes!(<ListParser>; items(input_str)?;
+        [number(ns)..lazy, ident(i)?] => {
+            let sum = ns.take(count).try_fold(0, |sum, n| n.map(|n| sum + u32::from(n)))?;
+            (sum, i)
+        }
+    ))
+}
+
+type Settings = (Option<String>, Vec<(String, u8)>);
+
+/// `pair` and `value` don't have methods: nested patterns look at their children directly.
+fn settings(input_str: &str) -> Result<Settings> {
+    Ok(match_nodes!(<ListParser>; items(input_str)?;
+        [ident(name)?, pair([ident(keys), value([number(values)])])..] => {
+            (name, keys.zip(values).collect())
+        }
+    ))
+}
+
+fn values(input_str: &str, env: &Env) -> Result<(Option<u8>, Vec<u8>)> {
+    Ok(match_nodes!(<ListParser>; items(input_str)?;
+        // Extra arguments are passed to each node of a `..` item.
+        [number(n)?, var(vs with env, 0)..] => (n, vs.collect()),
+    ))
+}
+
+fn var_settings(
+    input_str: &str,
+    env: &Env,
+    offset: u8,
+) -> Result<Vec<(String, u8)>> {
+    Ok(match_nodes!(<ListParser>; items(input_str)?;
+        [pair([ident(keys), value([var(values with env, offset)])])..] => {
+            keys.zip(values).collect()
+        }
+    ))
+}
+
+/// The numbers that fit in a `u8`, and how many errors were reported for the others.
+fn recovered(input_str: &str, limit: usize) -> (Option<Vec<u8>>, usize) {
+    let (numbers, errors) =
+        ListParser::parse_collecting_errors(Rule::file, input_str, |file| {
+            let list = file.into_children().next().unwrap();
+            Ok(match_nodes!(<ListParser>; list.into_children();
+                [number(ns)..recover] if limit < 3 => ns.take(limit).collect(),
+                [number(ns)..recover] => ns.collect(),
+            ))
+        });
+    (numbers, errors.len())
+}
+
+/// Parses the first item as a `string`, whatever its rule.
+fn first_string(input_str: &str) -> Result<String> {
+    let first = items(input_str)?.next().unwrap();
+    ListParser::string(first)
+}
+
+fn main() -> Result<()> {
+    assert_eq!(numbers("1 2 3")?, vec![1, 2, 3]);
+
+    // Optional items match greedily, from left to right.
+    assert_eq!(optional("1 x 2")?, (Some(1), Some("x".to_owned()), Some(2)));
+    assert_eq!(optional("x 2")?, (None, Some("x".to_owned()), Some(2)));
+    assert_eq!(optional("1")?, (Some(1), None, None));
+    assert_eq!(optional("")?, (None, None, None));
+    assert!(optional("x x").is_err());
+
+    // Several variable-length items.
+    assert_eq!(
+        numbers_and_idents("1 2 x y")?,
+        (vec![1, 2], vec!["x".to_owned(), "y".to_owned()])
+    );
+    assert_eq!(numbers_and_idents("x")?, (vec![], vec!["x".to_owned()]));
+    assert_eq!(numbers_and_idents("")?, (vec![], vec![]));
+    assert!(numbers_and_idents("x 1").is_err());
+    assert_eq!(
+        around_ident("1 x 2 3")?,
+        (vec![1], "x".to_owned(), vec![2, 3])
+    );
+    assert_eq!(around_ident("x")?, (vec![], "x".to_owned(), vec![]));
+    assert!(around_ident("1 2").is_err());
+
+    // A single item after a variable-length one, as before patterns could have several of them.
+    assert_eq!(last_ident("1 x y")?, (2, "y".to_owned()));
+    assert_eq!(last_ident("y")?, (0, "y".to_owned()));
+    assert!(last_ident("x 1").is_err());
+
+    // Guards.
+    assert_eq!(classify("12")?, "small 12");
+    assert_eq!(classify("1 2 3")?, "many numbers");
+    assert_eq!(classify("12345")?, "too long");
+    assert_eq!(classify("200")?, "big 200");
+    assert_eq!(classify("self")?, "self");
+    // The number is parsed before the `if` guard can be evaluated.
+    assert!(classify("300").is_err());
+    let error = classify("x").unwrap_err().to_string();
+    assert!(error.contains("found `[ident]`, which a guard rejected"));
+
+    // Alternative rules.
+    assert_eq!(
+        words("1 x 'a b' y")?,
+        (
+            Some(1),
+            vec!["x".to_owned(), "a b".to_owned(), "y".to_owned()]
+        )
+    );
+    assert_eq!(words("'z'")?, (None, vec!["z".to_owned()]));
+    assert!(words("x 1").is_err());
+
+    // Lazy variable-length items only parse the nodes that are used.
+    assert_eq!(sum_first("1 2 300 x", 2)?, (3, Some("x".to_owned())));
+    assert!(sum_first("1 2 300 x", 3).is_err());
+    assert_eq!(sum_first("", 3)?, (0, None));
+
+    // Nested patterns.
+    assert_eq!(
+        settings("main a=1 b=2")?,
+        (
+            Some("main".to_owned()),
+            vec![("a".to_owned(), 1), ("b".to_owned(), 2)]
+        )
+    );
+    assert_eq!(settings("")?, (None, vec![]));
+    let error = settings("a=1 b='x'").unwrap_err().to_string();
+    assert!(error.contains(
+        "expected `[ident?, pair([ident, value([number])])..]`, found `[pair, pair]`"
+    ));
+
+    // Extra arguments.
+    let env: Env = vec![("a", 1), ("b", 2)].into_iter().collect();
+    assert_eq!(values("3 $a $b", &env)?, (Some(3), vec![1, 2]));
+    assert_eq!(values("", &env)?, (None, vec![]));
+    let error = values("$a $c", &env).unwrap_err();
+    assert_eq!(error.variant.message(), "unbound variable `c`");
+    assert_eq!(error.location, InputLocation::Span((3, 5)));
+    assert_eq!(
+        var_settings("x=$a y=$b", &env, 10)?,
+        vec![("x".to_owned(), 11), ("y".to_owned(), 12)]
+    );
+    assert!(var_settings("x=$a y=3", &env, 10).is_err());
+
+    // Error recovery.
+    assert_eq!(recovered("1 300 2", 5), (Some(vec![1, 2]), 1));
+    assert_eq!(recovered("1 300 2", 1), (Some(vec![1]), 1));
+    assert_eq!(recovered("1 300 2 400", 5), (Some(vec![1, 2]), 2));
+    assert_eq!(recovered("1 a", 5), (None, 1));
+    assert!(around_ident("1 300 a").is_err());
+
+    // Errors from `#[from_str]` methods.
+    let error = optional("300").unwrap_err().to_string();
+    assert!(error.contains("number too large to fit in target type"));
+
+    // Nodes without a method are reported at their own span.
+    let error = numbers("1 a=2").unwrap_err();
+    assert_eq!(
+        error.variant.message(),
+        "Rule `pair` does not have a corresponding parsing method"
+    );
+    assert_eq!(error.location, InputLocation::Span((2, 5)));
+
+    // So are nodes given to the method of another rule.
+    assert_eq!(first_string("'a' b")?, "a");
+    let error = first_string("x 'a'").unwrap_err();
+    assert_eq!(
+        error.variant.message(),
+        "pest_consume::parser: called the `string` method on a node with rule `ident`"
+    );
+    assert_eq!(error.location, InputLocation::Span((0, 1)));
+
+    Ok(())
+}
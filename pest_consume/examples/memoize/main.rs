@@ -0,0 +1,53 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use pest_consume::{match_nodes, Error, Parser};
+
+type Result<T> = std::result::Result<T, Error<Rule>>;
+type Node<'i> = pest_consume::Node<'i, Rule, ()>;
+
+/// How many times the body of `product` ran.
+static PRODUCTS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Parser)]
+#[grammar = "../examples/memoize/grammar.pest"]
+struct ProductParser;
+
+#[pest_consume::parser]
+impl ProductParser {
+    #[from_str]
+    fn number(input: Node) -> Result<u64>;
+
+    #[memoize]
+    fn product(input: Node) -> Result<u64> {
+        PRODUCTS.fetch_add(1, Ordering::SeqCst);
+        Ok(match_nodes!(input.into_children();
+            [number(ns)..] => ns.product(),
+        ))
+    }
+
+    fn file(input: Node) -> Result<String> {
+        // When the guard fails, the second branch parses the `product` node again.
+        Ok(match_nodes!(input.into_children();
+            [product(p), EOI(_)] if p >= 1000 => format!("{} is large", p),
+            [product(p), EOI(_)] => format!("{} is small", p),
+        ))
+    }
+}
+
+fn parse_product(input_str: &str) -> Result<String> {
+    let inputs = ProductParser::parse(Rule::file, input_str)?;
+    ProductParser::file(inputs.single()?)
+}
+
+fn main() -> Result<()> {
+    assert_eq!(parse_product("2 * 3")?, "6 is small");
+    assert_eq!(PRODUCTS.load(Ordering::SeqCst), 1);
+    assert_eq!(parse_product("20 * 300")?, "6000 is large");
+    assert_eq!(PRODUCTS.load(Ordering::SeqCst), 2);
+
+    // The results are only kept during one parse.
+    assert_eq!(parse_product("2 * 3")?, "6 is small");
+    assert_eq!(PRODUCTS.load(Ordering::SeqCst), 3);
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use std::cell::RefCell;
+
+use pest_consume::{match_nodes, Error, Parser};
+
+type Result<T> = std::result::Result<T, Error<Rule>>;
+/// The user data is an arena where the fields are stored.
+type Node<'i, 'a> = pest_consume::Node<'i, Rule, &'a RefCell<Vec<String>>>;
+
+#[derive(Parser)]
+#[grammar = "../examples/csv/csv.pest"]
+struct CSVParser;
+
+#[pest_consume::parser]
+impl CSVParser {
+    fn EOI(_input: Node) -> Result<()> {
+        Ok(())
+    }
+
+    /// Stores the field in the arena, and returns its index.
+    fn field(input: Node) -> Result<usize> {
+        let mut arena = input.user_data_mut();
+        arena.push(input.as_str().to_owned());
+        Ok(arena.len() - 1)
+    }
+
+    fn record(input: Node) -> Result<Vec<usize>> {
+        Ok(match_nodes!(input.into_children();
+            [field(fields)..] => fields.collect(),
+        ))
+    }
+
+    fn file(input: Node) -> Result<Vec<Vec<usize>>> {
+        Ok(match_nodes!(input.into_children();
+            [record(records).., EOI(_)] => records.collect(),
+        ))
+    }
+}
+
+fn parse_into(
+    input_str: &str,
+    arena: &RefCell<Vec<String>>,
+) -> Result<Vec<Vec<usize>>> {
+    let input = CSVParser::parse_with_userdata(Rule::file, input_str, arena)?
+        .single()?;
+    CSVParser::file(input)
+}
+
+/// The same parser, with the arena passed as an extra argument instead of as user data.
+mod by_argument {
+    use pest_consume::match_nodes;
+
+    use super::{Result, Rule};
+
+    type Node<'i> = pest_consume::Node<'i, Rule, ()>;
+
+    pub(super) struct ArenaParser;
+
+    #[pest_consume::parser(parser = super::CSVParser)]
+    impl ArenaParser {
+        fn EOI(_input: Node, _arena: &mut Vec<String>) -> Result<()> {
+            Ok(())
+        }
+
+        fn field(input: Node, arena: &mut Vec<String>) -> Result<usize> {
+            arena.push(input.as_str().to_owned());
+            Ok(arena.len() - 1)
+        }
+
+        fn record(input: Node, arena: &mut Vec<String>) -> Result<Vec<usize>> {
+            Ok(match_nodes!(input.into_children();
+                [field(fields with arena)..] => fields.collect(),
+            ))
+        }
+
+        pub(super) fn file(
+            input: Node,
+            arena: &mut Vec<String>,
+        ) -> Result<Vec<Vec<usize>>> {
+            Ok(match_nodes!(input.into_children();
+                [record(records with arena).., EOI(_ with arena)] => {
+                    records.collect()
+                }
+            ))
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    // Each field sees the fields that its siblings and cousins added before it.
+    let arena = RefCell::new(Vec::new());
+    let ids = parse_into("1, 2\n3, 4", &arena)?;
+    assert_eq!(ids, vec![vec![0, 1], vec![2, 3]]);
+    assert_eq!(parse_into("5", &arena)?, vec![vec![4]]);
+    assert_eq!(arena.into_inner(), vec!["1", "2", "3", "4", "5"]);
+
+    // The same with a `&mut` argument.
+    let mut arena = Vec::new();
+    let input = CSVParser::parse(Rule::file, "1, 2\n3, 4")?.single()?;
+    let ids = by_argument::ArenaParser::file(input, &mut arena)?;
+    assert_eq!(ids, vec![vec![0, 1], vec![2, 3]]);
+    assert_eq!(arena, vec!["1", "2", "3", "4"]);
+
+    Ok(())
+}
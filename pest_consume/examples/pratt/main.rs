@@ -0,0 +1,88 @@
+use pest_consume::{match_nodes, Error, Parser};
+
+type Result<T> = std::result::Result<T, Error<Rule>>;
+type Node<'i> = pest_consume::Node<'i, Rule, ()>;
+
+#[derive(Parser)]
+#[grammar = "../examples/pratt/grammar.pest"]
+struct MathParser;
+
+#[pest_consume::parser]
+impl MathParser {
+    fn EOI(_input: Node) -> Result<()> {
+        Ok(())
+    }
+
+    fn number(input: Node) -> Result<i64> {
+        input.as_str().trim().parse().map_err(|e| input.error(e))
+    }
+
+    #[pratt(
+        term,
+        left(plus, minus),
+        left(times),
+        right(power),
+        prefix(neg),
+        postfix(fac)
+    )]
+    fn expr(l: i64, op: Node, r: i64) -> Result<i64> {
+        use Rule::*;
+        match op.as_rule() {
+            plus => Ok(l + r),
+            minus => Ok(l - r),
+            times => Ok(l * r),
+            power => Ok(l.pow(r as u32)),
+            r => Err(op.error(format!("Rule {:?} isn't an operator", r)))?,
+        }
+    }
+
+    #[pratt_prefix(expr)]
+    fn expr_prefix(op: Node, r: i64) -> Result<i64> {
+        match op.as_rule() {
+            Rule::neg => Ok(-r),
+            r => Err(op.error(format!("Rule {:?} isn't a prefix operator", r))),
+        }
+    }
+
+    #[pratt_postfix(expr)]
+    fn expr_postfix(l: i64, op: Node) -> Result<i64> {
+        match op.as_rule() {
+            Rule::fac => Ok((1..=l).product()),
+            r => {
+                Err(op.error(format!("Rule {:?} isn't a postfix operator", r)))
+            }
+        }
+    }
+
+    fn term(input: Node) -> Result<i64> {
+        Ok(match_nodes!(input.into_children();
+            [number(n)] => n,
+            [expr(n)] => n,
+        ))
+    }
+
+    fn calculation(input: Node) -> Result<i64> {
+        Ok(match_nodes!(input.into_children();
+            [expr(e), EOI(_)] => e,
+        ))
+    }
+}
+
+fn parse_math(input_str: &str) -> Result<i64> {
+    // Parse the input into `Nodes`
+    let inputs = MathParser::parse(Rule::calculation, input_str)?;
+    // There should be a single root node in the parsed tree
+    let input = inputs.single()?;
+    // Consume the `Node` recursively into the final value
+    MathParser::calculation(input)
+}
+
+fn main() -> Result<()> {
+    assert_eq!(parse_math("1 + 2 * 3")?, 7);
+    assert_eq!(parse_math("-1 - 2")?, -3);
+    assert_eq!(parse_math("2 ^ 3 ^ 2")?, 512);
+    assert_eq!(parse_math("3! * 2")?, 12);
+    assert_eq!(parse_math("-(1 + 2)!")?, -6);
+
+    Ok(())
+}
@@ -1,5 +1,3 @@
-use pest::prec_climber as pcl;
-use pest::prec_climber::PrecClimber;
 use pest_consume::{match_nodes, Error, Parser};
 
 type Result<T> = std::result::Result<T, Error<Rule>>;
@@ -9,16 +7,6 @@ type Node<'i> = pest_consume::Node<'i, Rule, ()>;
 #[grammar = "../examples/prec_climbing/grammar.pest"]
 struct MathParser;
 
-lazy_static::lazy_static! {
-    static ref PRECCLIMBER: PrecClimber<Rule> = PrecClimber::new(
-        vec![
-            pcl::Operator::new(Rule::plus, pcl::Assoc::Left),
-            pcl::Operator::new(Rule::minus, pcl::Assoc::Left),
-            pcl::Operator::new(Rule::times, pcl::Assoc::Left),
-        ]
-    );
-}
-
 #[pest_consume::parser]
 impl MathParser {
     fn EOI(_input: Node) -> Result<()> {
@@ -29,7 +17,7 @@ impl MathParser {
         input.as_str().trim().parse().map_err(|e| input.error(e))
     }
 
-    #[prec_climb(term, PRECCLIMBER)]
+    #[prec_climb(term, left(plus, minus), left(times))]
     fn expr(l: i64, op: Node, r: i64) -> Result<i64> {
         use Rule::*;
         match op.as_rule() {
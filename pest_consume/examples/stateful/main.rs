@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use pest_consume::{match_nodes, Error, Parser};
+
+type Result<T> = std::result::Result<T, Error<Rule>>;
+type Node<'i> = pest_consume::Node<'i, Rule, ()>;
+
+/// Interns the words of the input, so that each word is stored only once.
+#[derive(Parser, Default)]
+#[grammar = "../examples/stateful/grammar.pest"]
+struct Interner {
+    ids: HashMap<String, usize>,
+    words: Vec<String>,
+}
+
+#[pest_consume::parser(stateful)]
+impl Interner {
+    fn word(&mut self, input: Node) -> Result<usize> {
+        let word = input.as_str();
+        if let Some(&id) = self.ids.get(word) {
+            return Ok(id);
+        }
+        let id = self.words.len();
+        self.ids.insert(word.to_owned(), id);
+        self.words.push(word.to_owned());
+        Ok(id)
+    }
+
+    fn sentence(&mut self, input: Node) -> Result<Vec<usize>> {
+        // Calls `self.word(...)` for each node.
+        Ok(match_nodes!(self; input.into_children();
+            [word(ws)..] => ws.collect(),
+        ))
+    }
+
+    fn file(&mut self, input: Node) -> Result<Vec<Vec<usize>>> {
+        Ok(match_nodes!(self; input.into_children();
+            [sentence(ss).., EOI(_)] => ss.collect(),
+        ))
+    }
+
+    /// Looks the words up without interning them.
+    fn query(&self, input: Node) -> Result<Vec<Option<usize>>> {
+        Ok(match_nodes!(input.into_children();
+            [ws..] => ws.map(|w| self.ids.get(w.as_str()).copied()).collect(),
+        ))
+    }
+}
+
+/// Each parser needs its own module, since the macro generates some items next to it.
+mod counter {
+    use std::collections::HashMap;
+
+    use pest_consume::match_nodes;
+
+    use super::{Interner, Result, Rule};
+
+    /// Counts the occurrences of each word, ignoring the words given as user data.
+    #[derive(Default)]
+    pub(super) struct Counter {
+        pub(super) counts: HashMap<String, usize>,
+    }
+
+    type CounterNode<'i> = pest_consume::Node<'i, Rule, Vec<&'static str>>;
+
+    // The grammar is the same, so we can reuse the pest parser of `Interner`.
+    #[pest_consume::parser(stateful, parser = Interner)]
+    impl Counter {
+        /// Whether the word was counted.
+        fn word(&mut self, input: CounterNode) -> Result<bool> {
+            if input.user_data().contains(&input.as_str()) {
+                return Ok(false);
+            }
+            *self.counts.entry(input.as_str().to_owned()).or_insert(0) += 1;
+            Ok(true)
+        }
+
+        fn sentence(&mut self, input: CounterNode) -> Result<usize> {
+            Ok(match_nodes!(self; input.into_children();
+                [word(ws)..] => ws.filter(|&counted| counted).count(),
+            ))
+        }
+
+        /// The number of words that were counted.
+        pub(super) fn file(&mut self, input: CounterNode) -> Result<usize> {
+            Ok(match_nodes!(self; input.into_children();
+                [sentence(ss).., EOI(_)] => ss.sum(),
+            ))
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let mut interner = Interner::default();
+
+    // Methods taking `&mut self`.
+    let sentences =
+        interner.consume(Rule::file, "a b a.\nb c.", Interner::file)?;
+    assert_eq!(sentences, vec![vec![0, 1, 0], vec![1, 2]]);
+    assert_eq!(interner.words, vec!["a", "b", "c"]);
+
+    // Methods taking `&self`.
+    let found = interner.consume(Rule::query, "? c d", Interner::query)?;
+    assert_eq!(found, vec![Some(2), None]);
+    assert_eq!(interner.words.len(), 3);
+
+    // `match_nodes!` outside of the methods, given the instance.
+    let file = Interner::parse(Rule::file, "d a.")?.single()?;
+    let sentences: Vec<_> = match_nodes!(interner; <Interner>; file.into_children();
+        [sentence(ss).., _] => ss.collect(),
+    );
+    assert_eq!(sentences, vec![vec![3, 0]]);
+    assert_eq!(interner.words, vec!["a", "b", "c", "d"]);
+
+    // Stateful parsers with user data.
+    let mut counter = counter::Counter::default();
+    let counted = counter.consume_with_userdata(
+        Rule::file,
+        "a b a.\nb c a.",
+        vec!["b"],
+        counter::Counter::file,
+    )?;
+    assert_eq!(counted, 4);
+    assert_eq!(counter.counts.len(), 2);
+    assert_eq!((counter.counts["a"], counter.counts["c"]), (3, 1));
+
+    Ok(())
+}
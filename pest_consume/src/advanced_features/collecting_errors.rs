@@ -0,0 +1,53 @@
+//! ## Collecting several errors
+//!
+//! By default, parsing stops at the first error. When reporting errors to a user, it is often
+//! more helpful to report all the problems in the input at once.
+//!
+//! For that, consumer methods can report errors with [`Node::report_error`] and carry on, for
+//! example by returning a placeholder value. When parsing with
+//! [`Parser::parse_collecting_errors`], such errors are recorded and returned at the end, along
+//! with the result. When parsing normally, [`Node::report_error`] returns the error instead, so
+//! that it is propagated like any other error.
+//!
+//! ```ignore
+//! #[pest_consume::parser]
+//! impl CSVParser {
+//!     fn field(input: Node) -> Result<f64> {
+//!         match input.as_str().parse::<f64>() {
+//!             Ok(x) => Ok(x),
+//!             Err(e) => {
+//!                 // Only returns an error if errors are not being collected.
+//!                 input.report_error(input.error(e))?;
+//!                 Ok(f64::NAN)
+//!             }
+//!         }
+//!     }
+//!     ...
+//! }
+//!
+//! fn parse_csv(input_str: &str) -> (Option<Vec<Vec<f64>>>, Vec<Error<Rule>>) {
+//!     CSVParser::parse_collecting_errors(Rule::file, input_str, CSVParser::file)
+//! }
+//! ```
+//!
+//! Errors returned with `Err` are still fatal: they stop parsing, and
+//! [`Parser::parse_collecting_errors`] then returns `None` along with all the errors, the fatal
+//! one last.
+//!
+//! In [`match_nodes!`], a variable-length pattern can also skip the children that fail to parse,
+//! reporting their errors instead of stopping at the first one:
+//!
+//! ```ignore
+//!     fn file(input: Node) -> Result<Vec<Vec<f64>>> {
+//!         Ok(match_nodes!(input.into_children();
+//!             [record(records)..recover, EOI(_)] => records.collect(),
+//!         ))
+//!     }
+//! ```
+//!
+//! For a full example collecting errors, see [here][collecting_errors-example].
+//!
+//! [`match_nodes!`]: macro.match_nodes.html
+//! [`Node::report_error`]: struct.Node.html#method.report_error
+//! [`Parser::parse_collecting_errors`]: trait.Parser.html#method.parse_collecting_errors
+//! [collecting_errors-example]: https://github.com/Nadrieril/pest_consume/tree/master/pest_consume/examples/collecting_errors
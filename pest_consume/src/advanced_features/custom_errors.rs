@@ -0,0 +1,85 @@
+//! ## Custom error types
+//!
+//! By default, consumer methods return [`pest::error::Error`], which is also what [`Parser::parse`],
+//! [`Nodes::single`], [`Node::error`] and [`match_nodes!`] produce. If your application has its
+//! own error type, you can make the whole parser use it instead.
+//!
+//! For that, implement [`ParseError`] for your type. The only required method converts an error
+//! reported by pest; you can also override how errors pointing to a span of the input are built.
+//! Then pass the type to the [`parser`] macro, and add it as the last type parameter of `Node`.
+//!
+//! ```ignore
+//! #[derive(Debug)]
+//! enum MyError {
+//!     Syntax(pest_consume::Error<Rule>),
+//!     Invalid { offset: usize, message: String },
+//! }
+//!
+//! impl pest_consume::ParseError<Rule> for MyError {
+//!     fn from_pest_error(error: pest_consume::Error<Rule>) -> Self {
+//!         MyError::Syntax(error)
+//!     }
+//!     fn from_span(span: pest::Span<'_>, message: String) -> Self {
+//!         MyError::Invalid { offset: span.start(), message }
+//!     }
+//! }
+//!
+//! type Result<T> = std::result::Result<T, MyError>;
+//! type Node<'i> = pest_consume::Node<'i, Rule, (), MyError>;
+//!
+//! #[pest_consume::parser(error = MyError)]
+//! impl CSVParser {
+//!     fn field(input: Node) -> Result<f64> {
+//!         // `input.error` now returns a `MyError`
+//!         input.as_str().parse::<f64>().map_err(|e| input.error(e))
+//!     }
+//!     ...
+//! }
+//!
+//! fn parse_csv(input_str: &str) -> Result<Vec<Vec<f64>>> {
+//!     let inputs = CSVParser::parse(Rule::file, input_str)?;
+//!     let input = inputs.single()?;
+//!     CSVParser::file(input)
+//! }
+//! ```
+//!
+//! When parsing with [`Parser::parse_named`] or [`Parser::parse_file`], the name of the input is
+//! passed to [`ParseError::with_source_name`]; implement it to keep that name in your errors.
+//!
+//! ## Naming rules in error messages
+//!
+//! The errors produced by this crate and by pest mention rules by their name in the grammar,
+//! e.g. ``expected `[record..]`, found `[field, EOI]` ``. To show something friendlier to the
+//! users of your parser, give a rule a display name with an attribute on its method:
+//!
+//! ```ignore
+//! #[pest_consume::parser]
+//! impl CSVParser {
+//!     #[display_name = "a record"]
+//!     fn record(input: Node) -> Result<Vec<f64>> {
+//!         ...
+//!     }
+//!     #[display_name = "the end of the file"]
+//!     fn EOI(_input: Node) -> Result<()> {
+//!         Ok(())
+//!     }
+//! }
+//! ```
+//!
+//! The display names can also be looked up with [`Parser::rule_display_name`]. Rules without a
+//! method keep their name in the grammar.
+//!
+//! For a full example using display names, see [here][display_names-example].
+//!
+//! [`pest::error::Error`]: https://docs.rs/pest/2/pest/error/struct.Error.html
+//! [`ParseError::with_source_name`]: trait.ParseError.html#method.with_source_name
+//! [`Parser::parse_named`]: trait.Parser.html#method.parse_named
+//! [`Parser::parse_file`]: trait.Parser.html#method.parse_file
+//! [`Parser::rule_display_name`]: trait.Parser.html#method.rule_display_name
+//! [`ParseError`]: trait.ParseError.html
+//! [`parser`]: macro@crate::parser
+//! [`Nodes::single`]: struct.Nodes.html#method.single
+//! [`Node::error`]: struct.Node.html#method.error
+//! [`match_nodes!`]: macro.match_nodes.html
+//! [`Parser::parse`]: trait.Parser.html#method.parse
+//! [display_names-example]: https://github.com/Nadrieril/pest_consume/tree/master/pest_consume/examples/display_names
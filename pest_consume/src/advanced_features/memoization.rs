@@ -0,0 +1,33 @@
+//! ## Memoization
+//!
+//! A method can end up called several times on the same node: when a [`match_nodes!`] branch
+//! parses some nodes and then fails its `if` guard, the next branches parse them again. This is
+//! wasteful when the method does expensive work, like type checking or constant folding.
+//!
+//! With the `#[memoize]` attribute, a method saves the result of each successful call, and
+//! returns it again when called on the same node, i.e. a node with the same rule, span and depth
+//! in the tree. The results are kept for the duration of one call to [`Parser::parse`] (or any
+//! other method of [`Parser`] that parses an input), and are cloned when reused, so the return
+//! type of the method must implement `Clone`. For a value that is expensive to clone, return an
+//! `Rc` of it, like below.
+//!
+//! ```ignore
+//! #[pest_consume::parser]
+//! impl CSVParser {
+//!     #[memoize]
+//!     fn record(input: Node) -> Result<Rc<Vec<f64>>> {
+//!         ...
+//!     }
+//! }
+//! ```
+//!
+//! The results are stored as [`Any`], which means they must also be `'static`: a method returning
+//! e.g. `&'i str` can't be memoized. Errors are not saved, so a failing call is repeated.
+//!
+//! `#[memoize]` is not supported on methods with extra arguments, nor in stateful parsers, since
+//! their results could depend on more than the node.
+//!
+//! [`match_nodes!`]: macro.match_nodes.html
+//! [`Parser`]: trait.Parser.html
+//! [`Parser::parse`]: trait.Parser.html#method.parse
+//! [`Any`]: https://doc.rust-lang.org/std/any/trait.Any.html
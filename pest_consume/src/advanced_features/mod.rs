@@ -1,6 +1,10 @@
 //! Documentation for advanced features of this crate
 
+pub mod collecting_errors;
+pub mod custom_errors;
+pub mod memoization;
 pub mod prec_climbing;
 pub mod rule_aliasing;
 pub mod rule_shortcutting;
+pub mod stateful_parsers;
 pub mod user_data;
@@ -4,36 +4,28 @@
 //! The special `prec_climb` attribute can be placed on a rule method, and that method will automatically
 //! use precedence climbing.
 //!
-//! The attribute needs two arguments: the first is the name of the rule of the non-operator children of this rule.
+//! The first argument of the attribute is the name of the rule of the non-operator children of this rule.
 //! For example, if the rule definition is `expr = { term ~ (operator ~ term)* }`, the non-operator children of the rule
 //! `expr` have the rule `term`.
-//! The second argument is an expression that references a `pest` [`PrecClimber`].
-//! The usual way is to use [`lazy_static`] to define the [`PrecClimber`], to ensure we only construct it once.
+//! The following arguments are the table of operators, from lowest to highest precedence. Each entry is either
+//! `left(...)` or `right(...)` depending on the associativity, and lists the rules of the operators that have that
+//! precedence. The macro builds the corresponding `pest` [`PrecClimber`] once and caches it.
 //!
 //! Finally, the method itself must take three arguments: a [`Node`] containing the matched operator, and a left and a right value.
 //! The type of the values, the return type of the rule, and the return type of the children's rule must all be the same.
 //!
 //! ```ignore
-//! lazy_static::lazy_static! {
-//!     static ref PRECCLIMBER: PrecClimber<Rule> = PrecClimber::new(
-//!         vec![
-//!             pcl::Operator::new(Rule::plus, pcl::Assoc::Left),
-//!             pcl::Operator::new(Rule::minus, pcl::Assoc::Left),
-//!             pcl::Operator::new(Rule::times, pcl::Assoc::Left),
-//!         ]
-//!     );
-//! }
-//! ...
 //! #[pest_consume::parser]
 //! impl MathParser {
 //!     ...
-//!     #[prec_climb(term, PRECCLIMBER)]
+//!     #[prec_climb(term, left(plus, minus), left(times), right(power))]
 //!     fn expr(l: i64, op: Node, r: i64) -> Result<i64> {
 //!         use Rule::*;
 //!         match op.as_rule() {
 //!             plus => Ok(l + r),
 //!             minus => Ok(l - r),
 //!             times => Ok(l * r),
+//!             power => Ok(l.pow(r as u32)),
 //!             r => Err(op.error(format!("Rule {:?} isn't an operator", r)))?,
 //!         }
 //!     }
@@ -41,9 +33,45 @@
 //! }
 //! ```
 //!
+//! Alternatively, the second argument can be an expression that references a [`PrecClimber`] you
+//! built yourself, e.g. `#[prec_climb(term, PRECCLIMBER)]`.
+//!
 //! For a full example using precedence climbing, see [here][prec_climbing-example].
 //!
+//! ## Pratt parsing
+//!
+//! Precedence climbing only supports binary operators. For prefix and postfix operators, use the
+//! `pratt` attribute instead, which relies on a `pest` [`PrattParser`].
+//! It takes the same arguments as `prec_climb`, and the method handles infix operators in the same
+//! way. The operator table can additionally contain `prefix(...)` and `postfix(...)` entries.
+//! Prefix and postfix operators are handled by helper methods marked with
+//! `#[pratt_prefix(method)]` and `#[pratt_postfix(method)]`, where `method` is the one with the
+//! `pratt` attribute. Those helpers do not correspond to a rule.
+//!
+//! ```ignore
+//! #[pest_consume::parser]
+//! impl MathParser {
+//!     ...
+//!     #[pratt(term, left(plus, minus), left(times), prefix(neg), postfix(fac))]
+//!     fn expr(l: i64, op: Node, r: i64) -> Result<i64> {
+//!         ...
+//!     }
+//!     #[pratt_prefix(expr)]
+//!     fn expr_prefix(op: Node, r: i64) -> Result<i64> {
+//!         ...
+//!     }
+//!     #[pratt_postfix(expr)]
+//!     fn expr_postfix(l: i64, op: Node) -> Result<i64> {
+//!         ...
+//!     }
+//!     ...
+//! }
+//! ```
+//!
+//! For a full example using Pratt parsing, see [here][pratt-example].
+//!
 //! [`Node`]: struct.Node.html
 //! [`PrecClimber`]: https://docs.rs/pest/2.1.2/pest/prec_climber/struct.PrecClimber.html
-//! [`lazy_static`]: https://crates.io/crates/lazy_static
 //! [prec_climbing-example]: https://github.com/Nadrieril/pest_consume/tree/master/pest_consume/examples/prec_climbing
+//! [`PrattParser`]: https://docs.rs/pest/2.5/pest/pratt_parser/struct.PrattParser.html
+//! [pratt-example]: https://github.com/Nadrieril/pest_consume/tree/master/pest_consume/examples/pratt
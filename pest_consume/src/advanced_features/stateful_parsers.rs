@@ -0,0 +1,57 @@
+//! ## Stateful parsers
+//!
+//! The user data passed with [`Parser::parse_with_userdata`] is shared by every node, so
+//! mutating it requires [`RefCell`]. When the parser needs to build up some state, like a symbol
+//! table or a string interner, it is more natural to keep that state in the parser itself.
+//!
+//! With the `stateful` option, consumer methods take `&self` or `&mut self` before the `Node`.
+//! [`match_nodes!`] is given `self` first, so that it calls the methods on it, and parsing starts
+//! with [`Parser::consume`] on an instance of the parser.
+//!
+//! ```ignore
+//! #[derive(Parser, Default)]
+//! #[grammar = "../examples/csv/csv.pest"]
+//! struct CSVParser {
+//!     interner: HashMap<String, usize>,
+//! }
+//!
+//! #[pest_consume::parser(stateful)]
+//! impl CSVParser {
+//!     fn EOI(&self, _input: Node) -> Result<()> {
+//!         Ok(())
+//!     }
+//!     fn field(&mut self, input: Node) -> Result<usize> {
+//!         let next_id = self.interner.len();
+//!         Ok(*self.interner.entry(input.as_str().to_owned()).or_insert(next_id))
+//!     }
+//!     fn record(&mut self, input: Node) -> Result<Vec<usize>> {
+//!         // Calls `self.field(...)` for each node.
+//!         Ok(match_nodes!(self; input.into_children();
+//!             [field(fields)..] => fields.collect(),
+//!         ))
+//!     }
+//!     ...
+//! }
+//!
+//! fn parse_csv(input_str: &str) -> Result<Vec<Vec<usize>>> {
+//!     let mut parser = CSVParser::default();
+//!     parser.consume(Rule::file, input_str, CSVParser::file)
+//! }
+//! ```
+//!
+//! All the rule methods of a stateful parser must take `self` by reference, and either kind can be
+//! passed to [`Parser::consume`]. User data can be given with [`Parser::consume_with_userdata`].
+//! `match_nodes!` can also be used outside of the parser's methods, by passing the instance
+//! followed by the parser type, as in `match_nodes!(parser; <CSVParser>; nodes; ...)`. An
+//! invocation without an instance, as in `match_nodes!(<OtherParser>; nodes; ...)`, calls the
+//! methods of a stateless parser as usual. The `prec_climb` and `pratt` attributes are not
+//! supported in stateful parsers.
+//!
+//! For a full example of a stateful parser, see [here][stateful-example].
+//!
+//! [`match_nodes!`]: macro.match_nodes.html
+//! [`Parser::consume`]: trait.Parser.html#method.consume
+//! [`Parser::consume_with_userdata`]: trait.Parser.html#method.consume_with_userdata
+//! [`Parser::parse_with_userdata`]: trait.Parser.html#method.parse_with_userdata
+//! [`RefCell`]: https://doc.rust-lang.org/std/cell/struct.RefCell.html
+//! [stateful-example]: https://github.com/Nadrieril/pest_consume/tree/master/pest_consume/examples/stateful
@@ -9,10 +9,11 @@
 //! during parsing via [`Node::user_data`].
 //!
 //! The type of the user data is the second type parameter in the types of `Node<'i, Rule, Data>` and `Nodes<'i, Rule, Data>`.
-//! The data needs to be `Clone`, and will be cloned often so it should be cheap to clone.
-//! A common usage is to have this data be a reference, which are free to clone.
+//! The data is shared by all the nodes of a parse, so it doesn't need to be `Clone`.
+//! A common usage is to have this data be a reference.
 //!
-//! If you need mutable access to some data, use [`Cell`] or [`RefCell`].
+//! If you need mutable access to some data, use [`Cell`] or [`RefCell`] (see
+//! [below](#mutable-data)), or make the parser [stateful](../stateful_parsers/index.html).
 //!
 //! ```ignore
 //! struct AppSettings { ... }
@@ -48,9 +49,105 @@
 //! }
 //! ```
 //!
+//! ## Generic user data
+//!
+//! The same consumer methods can be used with different kinds of user data, e.g. `()` in tests
+//! and an interner in production, by making the impl block generic over it:
+//!
+//! ```ignore
+//! type Node<'i, D> = pest_consume::Node<'i, Rule, D>;
+//!
+//! trait Interner {
+//!     fn intern(&self, s: &str) -> Symbol;
+//! }
+//!
+//! #[pest_consume::parser]
+//! impl<D: Interner> CSVParser {
+//!     fn field(input: Node<D>) -> Result<Symbol> {
+//!         Ok(input.user_data().intern(input.as_str()))
+//!     }
+//!     ...
+//! }
+//! ```
+//!
+//! Since the parser type doesn't mention `D`, the [`parser`] macro moves such generic parameters,
+//! and the bounds that involve them, to each method that uses them. The type of the user data is
+//! then inferred from the `Node` that is passed to the method.
+//!
+//! ## Mutable data
+//!
+//! Since the user data is shared by all the nodes, it can't be a `&mut` reference. When it is a
+//! [`RefCell`], or a reference to one, [`Node::user_data_mut`] borrows its contents mutably:
+//!
+//! ```ignore
+//! type Node<'i, 'a> = pest_consume::Node<'i, Rule, &'a RefCell<Arena>>;
+//!
+//! #[pest_consume::parser]
+//! impl CSVParser {
+//!     fn field(input: Node) -> Result<FieldId> {
+//!         Ok(input.user_data_mut().alloc(input.as_str()))
+//!     }
+//!     ...
+//! }
+//! ```
+//!
+//! The borrow must not be held while parsing child nodes, since they would then fail to borrow
+//! the data again.
+//!
+//! To give the consuming methods mutable access to something like an arena without [`RefCell`],
+//! pass it as an extra argument instead. Consumer methods can take arguments after the `Node`, and
+//! [`match_nodes!`] passes them along with `with`:
+//!
+//! ```ignore
+//! fn parse_with_arena(input_str: &str, arena: &mut Arena) -> Result<Vec<Vec<FieldId>>> {
+//!     let inputs = CSVParser::parse(Rule::file, input_str)?;
+//!     let input = inputs.single()?;
+//!     CSVParser::file(input, arena)
+//! }
+//!
+//! #[pest_consume::parser]
+//! impl CSVParser {
+//!     fn field(input: Node, arena: &mut Arena) -> Result<FieldId> {
+//!         Ok(arena.alloc(input.as_str()))
+//!     }
+//!     fn record(input: Node, arena: &mut Arena) -> Result<Vec<FieldId>> {
+//!         Ok(match_nodes!(input.into_children();
+//!             [field(fields with arena)..] => fields.collect(),
+//!         ))
+//!     }
+//!     ...
+//! }
+//! ```
+//!
+//! The reference is reborrowed for each node, so the data is threaded through the whole
+//! consumption. Keep in mind that a branch with an `if` guard parses its nodes before the guard
+//! is checked, so they may be parsed again by a later branch.
+//!
+//! Methods with the `prec_climb` or `pratt` attribute can take extra arguments too, after the
+//! three operands. They are then passed along to the methods for the operands and the operators:
+//!
+//! ```ignore
+//! #[prec_climb(term, CLIMBER)]
+//! fn expr(left: ExprId, op: Node, right: ExprId, arena: &mut Arena) -> Result<ExprId> {
+//!     Ok(arena.alloc_binop(left, op.as_rule(), right))
+//! }
+//! fn term(input: Node, arena: &mut Arena) -> Result<ExprId> {
+//!     ...
+//! }
+//! ```
+//!
+//! Here the generated `expr` method takes `(input: Node, arena: &mut Arena)`. Alternatively, the
+//! data can be kept in the parser itself with a [stateful parser](../stateful_parsers/index.html).
+//!
+//! For a full example of both ways of mutating data, see [here][mutable_data-example].
+//!
+//! [`match_nodes!`]: macro.match_nodes.html
+//! [`parser`]: macro@crate::parser
 //! [`Nodes`]: struct.Nodes.html
 //! [`Node::user_data`]: struct.Node.html#method.user_data
+//! [`Node::user_data_mut`]: struct.Node.html#method.user_data_mut
 //! [`Parser::parse`]: trait.Parser.html#method.parse
 //! [`Parser::parse_with_userdata`]: trait.Parser.html#method.parse_with_userdata
 //! [`Cell`]: https://doc.rust-lang.org/std/cell/struct.Cell.html
 //! [`RefCell`]: https://doc.rust-lang.org/std/cell/struct.RefCell.html
+//! [mutable_data-example]: https://github.com/Nadrieril/pest_consume/tree/master/pest_consume/examples/mutable_data
@@ -0,0 +1,140 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use pest::error::{Error, ErrorVariant};
+use pest::{RuleType, Span};
+
+use crate::{Diagnostic, ParseError};
+
+/// Identifies a node by its rule, the start and end of its span, and its depth in the tree.
+pub(crate) type NodeKey<R> = (R, usize, usize, usize);
+
+/// State shared by all the nodes that come from the same call to the parser.
+pub(crate) struct Context<R, E> {
+    /// Where non-fatal errors go when they are collected, as with
+    /// [`Parser::parse_collecting_errors`](trait.Parser.html#method.parse_collecting_errors).
+    /// `None` when they aren't, in which case they are returned as normal errors.
+    errors: Option<RefCell<Vec<E>>>,
+    /// The name of the input, e.g. its path, to mention in errors.
+    source_name: Option<String>,
+    /// The names of rules to use in errors, as given by
+    /// [`Parser::rule_display_name`](trait.Parser.html#method.rule_display_name).
+    rule_display_name: fn(R) -> Option<&'static str>,
+    /// The results of `#[memoize]` methods, keyed on the rule, span and depth of their node.
+    memoized: RefCell<HashMap<NodeKey<R>, Box<dyn Any>>>,
+}
+
+impl<R: RuleType, E> Context<R, E> {
+    pub(crate) fn new() -> Self {
+        Context {
+            errors: None,
+            source_name: None,
+            rule_display_name: |_| None,
+            memoized: RefCell::new(HashMap::new()),
+        }
+    }
+    pub(crate) fn collecting_errors(mut self) -> Self {
+        self.errors = Some(RefCell::new(Vec::new()));
+        self
+    }
+    pub(crate) fn with_source_name(mut self, name: &str) -> Self {
+        self.source_name = Some(name.to_owned());
+        self
+    }
+    pub(crate) fn with_rule_display_names(
+        mut self,
+        rule_display_name: fn(R) -> Option<&'static str>,
+    ) -> Self {
+        self.rule_display_name = rule_display_name;
+        self
+    }
+
+    pub(crate) fn report_error(&self, error: E) -> Result<(), E> {
+        match &self.errors {
+            Some(errors) => {
+                errors.borrow_mut().push(error);
+                Ok(())
+            }
+            None => Err(error),
+        }
+    }
+    pub(crate) fn memoized<T: Clone + 'static>(
+        &self,
+        key: NodeKey<R>,
+    ) -> Option<T> {
+        self.memoized
+            .borrow()
+            .get(&key)
+            .and_then(|value| value.downcast_ref::<T>())
+            .cloned()
+    }
+    pub(crate) fn memoize<T: 'static>(&self, key: NodeKey<R>, value: T) {
+        self.memoized.borrow_mut().insert(key, Box::new(value));
+    }
+
+    /// Returns the errors collected so far.
+    pub(crate) fn take_errors(&self) -> Vec<E> {
+        match &self.errors {
+            Some(errors) => errors.take(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The name of `rule` to show in errors.
+    pub(crate) fn rule_name(&self, rule: R) -> String {
+        match (self.rule_display_name)(rule) {
+            Some(name) => name.to_owned(),
+            None => format!("{:?}", rule),
+        }
+    }
+
+    fn name_error(&self, error: E) -> E
+    where
+        E: ParseError<R>,
+    {
+        match &self.source_name {
+            Some(name) => error.with_source_name(name),
+            None => error,
+        }
+    }
+    pub(crate) fn error(&self, span: Span, message: String) -> E
+    where
+        E: ParseError<R>,
+    {
+        self.name_error(E::from_span(span, message))
+    }
+    pub(crate) fn pest_error(&self, error: Error<R>) -> E
+    where
+        E: ParseError<R>,
+    {
+        // Renaming turns the error into a custom one, so only do it when it changes something.
+        let has_display_names = match &error.variant {
+            ErrorVariant::ParsingError {
+                positives,
+                negatives,
+            } => positives
+                .iter()
+                .chain(negatives)
+                .any(|rule| (self.rule_display_name)(*rule).is_some()),
+            ErrorVariant::CustomError { .. } => false,
+        };
+        let error = if has_display_names {
+            error.renamed_rules(|rule| self.rule_name(*rule))
+        } else {
+            error
+        };
+        self.name_error(E::from_pest_error(error))
+    }
+    pub(crate) fn diagnostic<'i>(
+        &self,
+        span: Span<'i>,
+        message: String,
+    ) -> Diagnostic<'i> {
+        let diagnostic = Diagnostic::new(span, message);
+        match &self.source_name {
+            Some(name) => diagnostic.with_source_name(name),
+            None => diagnostic,
+        }
+    }
+}
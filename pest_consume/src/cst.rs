@@ -0,0 +1,76 @@
+/// Generates a typed concrete syntax tree from a pest grammar.
+///
+/// Instead of consuming the parse tree into your own types, you can walk it through typed
+/// wrappers around [`Node`]: this macro reads the grammar at compile time, and generates a struct
+/// for each (non-silent) rule, with a method for each of its children.
+///
+/// ```ignore
+/// // field = { (ASCII_DIGIT | "." | "-")+ }
+/// // record = { field ~ ("," ~ field)* }
+/// // file = { SOI ~ (record ~ ("\r\n" | "\n"))* ~ EOI }
+/// #[derive(pest_consume::Parser)]
+/// #[grammar = "../examples/csv/csv.pest"]
+/// struct CSVParser;
+///
+/// // Implements `pest_consume::Parser`; no consumer method is needed.
+/// #[pest_consume::parser]
+/// impl CSVParser {}
+///
+/// mod cst {
+///     use super::Rule;
+///     pest_consume::cst!(grammar = "../examples/csv/csv.pest");
+/// }
+///
+/// fn parse_csv(input_str: &str) -> Result<Vec<Vec<f64>>> {
+///     use std::convert::TryFrom;
+///     let node = CSVParser::parse(Rule::file, input_str)?.single()?;
+///     let file = cst::File::try_from(node)?;
+///     file.record()
+///         .into_iter()
+///         .map(|record| {
+///             record.field()
+///                 .into_iter()
+///                 .map(|field| field.as_str().parse().map_err(|e| field.node().error(e)))
+///                 .collect()
+///         })
+///         .collect()
+/// }
+/// ```
+///
+/// The path of the grammar is resolved the same way as the `pest_derive` `grammar` attribute,
+/// and the enum of the rules is the one named `Rule` in scope. It is best to call the macro in a
+/// module of its own, since it defines a type for each rule.
+///
+/// # Generated types
+///
+/// The struct for a rule is named after it in CamelCase, e.g. `Record` for `record`, and wraps a
+/// [`Node`] with that rule. Like `Node`, it takes the type of the user data and of the errors as
+/// type parameters, which default to `()` and [`Error`]. It has:
+/// - `try_from`, from [`TryFrom`], that checks the rule of the node;
+/// - `node`, `into_node`, `as_str` and `as_span` to get the underlying node;
+/// - a method named after each rule that can appear as a child, that returns the corresponding
+///   wrapper. Its return type follows the grammar: it returns an `Option` if the child may be
+///   missing, e.g. because of a `?`, and a `Vec` if it can appear several times, e.g. because of a
+///   `*`. Otherwise it returns a `Result`, which is only an error if the node was parsed with a
+///   grammar that doesn't match the one given to the macro.
+///
+/// Silent rules are inlined in the rules that use them, and atomic rules have no children. The
+/// structs also implement [`FromNode`], so that a parser method declared with `#[from_node]` can
+/// return one.
+///
+/// Node tags (`#tag = expr`) don't change the children of a rule. When `pest_derive` handles
+/// them, i.e. with its `grammar-extras` feature, enable the `grammar-extras` feature of this crate
+/// as well, which enables both.
+///
+/// [`Node`]: struct.Node.html
+/// [`Error`]: struct.Error.html
+/// [`FromNode`]: trait.FromNode.html
+/// [`TryFrom`]: https://doc.rust-lang.org/std/convert/trait.TryFrom.html
+// We wrap the proc-macro in a macro here because I want to write the doc in this crate.
+#[macro_export]
+macro_rules! cst {
+    ($($x:tt)*) => {
+        $crate::cst_!($($x)*);
+    };
+}
+pub use pest_consume_macros::cst as cst_;
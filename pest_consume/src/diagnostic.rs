@@ -0,0 +1,288 @@
+use std::fmt;
+
+use pest::error::Error;
+use pest::{RuleType, Span};
+
+use crate::ParseError;
+
+/// An error message with more context than a plain error: labels on the relevant parts of the
+/// input, notes and help messages.
+///
+/// It is usually created with [`Node::diagnostic`], and then turned into an error with
+/// [`ParseError::from_diagnostic`] or `into()`. Its `Display` implementation renders it in the
+/// style of rustc:
+///
+/// ```text
+/// error: duplicate field `x`
+///  --> 3:1
+///   |
+/// 1 | x = 1
+///   | - first defined here
+/// 2 | y = 2
+/// 3 | x = 3
+///   | ^ defined again here
+///   |
+///   = help: remove one of the definitions
+/// ```
+///
+/// A span that covers several lines is underlined on each of them, with its label under the last
+/// one.
+///
+/// ```ignore
+/// fn fields(input: Node) -> Result<HashMap<String, Node>> {
+///     let mut fields = HashMap::new();
+///     for field in input.into_children() {
+///         let name = field.as_str().to_owned();
+///         if let Some(previous) = fields.get(&name) {
+///             return Err(field
+///                 .diagnostic(format!("duplicate field `{}`", name))
+///                 .with_label("defined again here")
+///                 .with_secondary_label(previous.as_span(), "first defined here")
+///                 .with_help("remove one of the definitions")
+///                 .into());
+///         }
+///         fields.insert(name, field);
+///     }
+///     Ok(fields)
+/// }
+/// ```
+///
+/// [`Node::diagnostic`]: struct.Node.html#method.diagnostic
+/// [`ParseError::from_diagnostic`]: trait.ParseError.html#method.from_diagnostic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic<'i> {
+    message: String,
+    span: Span<'i>,
+    label: Option<String>,
+    secondary_labels: Vec<(Span<'i>, String)>,
+    notes: Vec<String>,
+    help: Vec<String>,
+    source_name: Option<String>,
+}
+
+impl<'i> Diagnostic<'i> {
+    /// Creates a diagnostic that points to `span`.
+    pub fn new<S: ToString>(span: Span<'i>, message: S) -> Self {
+        Diagnostic {
+            message: message.to_string(),
+            span,
+            label: None,
+            secondary_labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            source_name: None,
+        }
+    }
+    /// Adds a label to the span the diagnostic points to.
+    pub fn with_label<S: ToString>(mut self, label: S) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+    /// Adds a label to another span, e.g. to show where something was first defined.
+    pub fn with_secondary_label<S: ToString>(
+        mut self,
+        span: Span<'i>,
+        label: S,
+    ) -> Self {
+        self.secondary_labels.push((span, label.to_string()));
+        self
+    }
+    /// Adds a note, for information that isn't tied to a span.
+    pub fn with_note<S: ToString>(mut self, note: S) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+    /// Adds a help message, usually to suggest a fix.
+    pub fn with_help<S: ToString>(mut self, help: S) -> Self {
+        self.help.push(help.to_string());
+        self
+    }
+    /// Sets the name of the input, e.g. its path, to show next to the line and column.
+    pub fn with_source_name<S: ToString>(mut self, name: S) -> Self {
+        self.source_name = Some(name.to_string());
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    pub fn span(&self) -> Span<'i> {
+        self.span
+    }
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+    pub fn secondary_labels(&self) -> &[(Span<'i>, String)] {
+        &self.secondary_labels
+    }
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+    pub fn help(&self) -> &[String] {
+        &self.help
+    }
+    pub fn source_name(&self) -> Option<&str> {
+        self.source_name.as_deref()
+    }
+
+    /// The message and the label of the main span, followed by the other labels, notes and help
+    /// messages on separate lines. This is what is kept when converting to an error that only has
+    /// room for one span.
+    pub fn flattened_message(&self) -> String {
+        let mut message = self.message.clone();
+        if let Some(label) = &self.label {
+            message.push_str(&format!(": {}", label));
+        }
+        for (span, label) in &self.secondary_labels {
+            let (line, col) = span.start_pos().line_col();
+            message.push_str(&format!("\n{}:{}: {}", line, col, label));
+        }
+        for note in &self.notes {
+            message.push_str(&format!("\nnote: {}", note));
+        }
+        for help in &self.help {
+            message.push_str(&format!("\nhelp: {}", help));
+        }
+        message
+    }
+}
+
+/// A label to draw under a line of the input.
+struct Annotation<'a> {
+    line: usize,
+    // 1-based columns, in characters, with `end_col` exclusive.
+    start_col: usize,
+    end_col: usize,
+    is_primary: bool,
+    label: Option<&'a str>,
+}
+
+/// Returns the lines that `span` covers, without their line ending, each with the annotation that
+/// underlines the part of `span` within that line. The label goes under the last line, and lines
+/// in the middle of the span that are blank are left out.
+fn annotate<'a>(
+    span: Span<'a>,
+    is_primary: bool,
+    label: Option<&'a str>,
+) -> Vec<(&'a str, Annotation<'a>)> {
+    let input = span.get_input();
+    let mut annotations = Vec::new();
+    let mut line_start = input[..span.start()].rfind('\n').map_or(0, |i| i + 1);
+    let mut line = span.start_pos().line_col().0;
+    loop {
+        let line_end = input[line_start..]
+            .find('\n')
+            .map_or(input.len(), |i| line_start + i);
+        let line_text = input[line_start..line_end].trim_end_matches('\r');
+        // A span that ends with a line ending doesn't cover the next line.
+        let is_last = span.end() <= line_end + 1 || line_end == input.len();
+        let start = if line_start <= span.start() {
+            span.start()
+        } else {
+            // Continuation lines are underlined from their first non-blank character.
+            line_start + (line_text.len() - line_text.trim_start().len())
+        };
+        let end = span.end().min(line_start + line_text.len());
+        if start == span.start() || start < end {
+            let start_col = input[line_start..start].chars().count() + 1;
+            let len = input[start..end.max(start)].chars().count();
+            annotations.push((
+                line_text,
+                Annotation {
+                    line,
+                    start_col,
+                    // Always underline at least one character.
+                    end_col: start_col + len.max(1),
+                    is_primary,
+                    label: None,
+                },
+            ));
+        }
+        if is_last {
+            break;
+        }
+        line_start = line_end + 1;
+        line += 1;
+    }
+    annotations.last_mut().unwrap().1.label = label;
+    annotations
+}
+
+impl<'i> fmt::Display for Diagnostic<'i> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut lines: Vec<(&str, Annotation)> = Vec::new();
+        lines.extend(annotate(self.span, true, self.label.as_deref()));
+        for (span, label) in &self.secondary_labels {
+            lines.extend(annotate(*span, false, Some(label)));
+        }
+        lines.sort_by_key(|(_, a)| (a.line, !a.is_primary, a.start_col));
+
+        let (line, col) = self.span.start_pos().line_col();
+        let width = lines.last().unwrap().1.line.to_string().len();
+        let gutter = " ".repeat(width);
+        writeln!(f, "error: {}", self.message)?;
+        match &self.source_name {
+            Some(name) => {
+                writeln!(f, "{}--> {}:{}:{}", gutter, name, line, col)?
+            }
+            None => writeln!(f, "{}--> {}:{}", gutter, line, col)?,
+        }
+        write!(f, "{} |", gutter)?;
+
+        let mut previous_line = None;
+        for (text, annotation) in &lines {
+            if previous_line != Some(annotation.line) {
+                // Like rustc, show a single skipped line, and elide longer gaps.
+                match previous_line {
+                    Some(previous) if annotation.line == previous + 2 => {
+                        let skipped = self
+                            .span
+                            .get_input()
+                            .lines()
+                            .nth(previous)
+                            .unwrap_or("");
+                        let line =
+                            format!("{:>width$} | {}", previous + 1, skipped);
+                        write!(f, "\n{}", line.trim_end())?;
+                    }
+                    Some(previous) if annotation.line > previous + 2 => {
+                        write!(f, "\n...")?;
+                    }
+                    _ => {}
+                }
+                let line = format!("{:>width$} | {}", annotation.line, text);
+                write!(f, "\n{}", line.trim_end())?;
+                previous_line = Some(annotation.line);
+            }
+            let marker = if annotation.is_primary { "^" } else { "-" };
+            let underline = format!(
+                "{}{}",
+                " ".repeat(annotation.start_col - 1),
+                marker.repeat(annotation.end_col - annotation.start_col),
+            );
+            match annotation.label {
+                Some(label) => {
+                    write!(f, "\n{} | {} {}", gutter, underline, label)?
+                }
+                None => write!(f, "\n{} | {}", gutter, underline)?,
+            }
+        }
+
+        if !self.notes.is_empty() || !self.help.is_empty() {
+            write!(f, "\n{} |", gutter)?;
+        }
+        for note in &self.notes {
+            write!(f, "\n{} = note: {}", gutter, note)?;
+        }
+        for help in &self.help {
+            write!(f, "\n{} = help: {}", gutter, help)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'i, R: RuleType> From<Diagnostic<'i>> for Error<R> {
+    fn from(diagnostic: Diagnostic<'i>) -> Self {
+        Error::from_diagnostic(diagnostic)
+    }
+}
@@ -0,0 +1,111 @@
+use pest::RuleType;
+
+use crate::Node;
+
+/// A type that can be built directly from a [`Node`]. Usually derived with
+/// `#[derive(pest_consume::FromNode)]`.
+///
+/// Writing a `match_nodes!` body only to fill in the fields of a struct gets repetitive. Instead,
+/// the struct can say which rule it is built from, and the derive works out how to fill in each
+/// field from the type of the field:
+/// ```ignore
+/// // field = { (ASCII_DIGIT | "." | "-")+ }
+/// // record = { field ~ ("," ~ field)* }
+/// // file = { SOI ~ (record ~ ("\r\n" | "\n"))* ~ EOI }
+/// #[derive(pest_consume::FromNode)]
+/// #[rule(file)]
+/// struct File {
+///     records: Vec<Record>,
+/// }
+///
+/// #[derive(pest_consume::FromNode)]
+/// #[rule(record)]
+/// struct Record<'i> {
+///     #[rule(field)]
+///     fields: Vec<f64>,
+///     text: &'i str,
+/// }
+/// ```
+///
+/// # Structs
+///
+/// A struct has a `#[rule(...)]` attribute, and is built from a node with that rule. Its fields
+/// are, depending on their type and attributes:
+/// - `&'i str` or `Span<'i>`: the text or the span of the node itself;
+/// - `#[from_str] T`: the text of the node itself, parsed with [`str::parse`];
+/// - `#[rule(...)] T`: a child with the given rule. `T` is `&'i str`, `Span<'i>`, or a type that
+///   is parsed from the text of the child with [`str::parse`];
+/// - any other type: a child built with `FromNode`, e.g. another derived type.
+///
+/// A field that stands for a child can be wrapped in an `Option` or a `Vec`, to match zero or one
+/// child, or any number of them; the type inside can be a `Box`. The fields that stand for
+/// children are matched against the children of the node in order, like the items of a
+/// `match_nodes!` pattern, and a trailing `EOI` child is ignored. If no field stands for a child,
+/// the children are not looked at.
+///
+/// The enum of the rules is the one named `Rule` in scope, unless the rule is given as a path,
+/// like `#[rule(grammar::Rule::record)]`. A lifetime parameter of the type is the lifetime of the
+/// input.
+///
+/// # Enums
+///
+/// Each variant of an enum matches nodes of its own rule(s):
+/// ```ignore
+/// // expr = { number | ident | paren }
+/// #[derive(pest_consume::FromNode)]
+/// #[rule(expr)]
+/// enum Expr<'i> {
+///     #[rule(number)]
+///     Number(#[from_str] f64),
+///     #[rule(ident)]
+///     Ident(&'i str),
+///     Paren(Box<Paren<'i>>),
+/// }
+/// ```
+/// A variant with a `#[rule(...)]` attribute is built from a node with that rule, and its fields
+/// are filled in as for a struct. A variant with a single field and no attribute, like `Paren`
+/// above, is built from any node that the type of its field can be built from.
+///
+/// With a `#[rule(...)]` attribute on the enum, the enum is built from a node with that rule, and
+/// the variant is chosen from its only child. Without one, the variant is chosen from the node
+/// itself, which suits rules that are a silent choice like `_{ number | ident | paren }`.
+///
+/// # Using it in a parser
+///
+/// A method of the parser can delegate to `FromNode`, so that the type can be used in
+/// `match_nodes!` like any other rule. The method is declared without a body, with the
+/// `#[from_node]` attribute:
+/// ```ignore
+/// #[pest_consume::parser]
+/// impl CSVParser {
+///     #[from_node]
+///     fn record(input: Node) -> Result<Record>;
+///     ...
+/// }
+/// ```
+///
+/// [`Node`]: struct.Node.html
+pub trait FromNode<'i, R: RuleType, D, E>: Sized {
+    /// The rules of the nodes that `Self` can be built from.
+    fn rules() -> Vec<R>;
+    /// Whether `Self` can be built from a node with rule `rule`.
+    fn matches_rule(rule: R) -> bool {
+        Self::rules().contains(&rule)
+    }
+    /// Builds a value from `node`.
+    fn from_node(node: Node<'i, R, D, E>) -> Result<Self, E>;
+}
+
+impl<'i, R: RuleType, D, E, T: FromNode<'i, R, D, E>> FromNode<'i, R, D, E>
+    for Box<T>
+{
+    fn rules() -> Vec<R> {
+        T::rules()
+    }
+    fn matches_rule(rule: R) -> bool {
+        T::matches_rule(rule)
+    }
+    fn from_node(node: Node<'i, R, D, E>) -> Result<Self, E> {
+        T::from_node(node).map(Box::new)
+    }
+}
@@ -117,6 +117,132 @@
 //!
 //! The case of the `file` rule is similar.
 //!
+//! # Checking that every rule is handled
+//!
+//! By default, a missing method is only noticed when a node with the corresponding rule is
+//! encountered during parsing. Passing the grammar file to the [`parser`] macro with the
+//! `exhaustive` option makes it check at compile time that every (non-silent) rule has a
+//! method, and that every method corresponds to a rule. The path is resolved the same way
+//! as the `pest_derive` `grammar` attribute.
+//!
+//! ```ignore
+//! #[pest_consume::parser(grammar = "../examples/csv/csv.pest", exhaustive)]
+//! impl CSVParser {
+//!     ...
+//! }
+//! ```
+//!
+//! For example, forgetting the method for `record` is then an error:
+//!
+//! ```compile_fail
+//! # use pest_consume::{Error, Parser};
+//! # type Result<T> = std::result::Result<T, Error<Rule>>;
+//! # type Node<'i> = pest_consume::Node<'i, Rule, ()>;
+//! # #[derive(Parser)]
+//! # #[grammar = "../examples/csv/csv.pest"]
+//! # struct CSVParser;
+//! // error: the following rules do not have a corresponding method: record
+//! #[pest_consume::parser(grammar = "../examples/csv/csv.pest", exhaustive)]
+//! impl CSVParser {
+//!     fn EOI(_input: Node) -> Result<()> {
+//!         Ok(())
+//!     }
+//!     fn number(_input: Node) -> Result<()> {
+//!         Ok(())
+//!     }
+//!     fn string(_input: Node) -> Result<()> {
+//!         Ok(())
+//!     }
+//!     fn field(_input: Node) -> Result<()> {
+//!         Ok(())
+//!     }
+//!     fn file(_input: Node) -> Result<()> {
+//!         Ok(())
+//!     }
+//! }
+//! # fn main() {}
+//! ```
+//!
+//! and so is a method that doesn't correspond to a rule, e.g. because of a typo:
+//!
+//! ```compile_fail
+//! # use pest_consume::{Error, Parser};
+//! # type Result<T> = std::result::Result<T, Error<Rule>>;
+//! # type Node<'i> = pest_consume::Node<'i, Rule, ()>;
+//! # #[derive(Parser)]
+//! # #[grammar = "../examples/csv/csv.pest"]
+//! # struct CSVParser;
+//! #[pest_consume::parser(grammar = "../examples/csv/csv.pest", exhaustive)]
+//! impl CSVParser {
+//!     fn EOI(_input: Node) -> Result<()> {
+//!         Ok(())
+//!     }
+//!     fn number(_input: Node) -> Result<()> {
+//!         Ok(())
+//!     }
+//!     fn string(_input: Node) -> Result<()> {
+//!         Ok(())
+//!     }
+//!     fn field(_input: Node) -> Result<()> {
+//!         Ok(())
+//!     }
+//!     fn record(_input: Node) -> Result<()> {
+//!         Ok(())
+//!     }
+//!     fn file(_input: Node) -> Result<()> {
+//!         Ok(())
+//!     }
+//!     // error: method `recrod` does not correspond to a rule of the grammar
+//!     fn recrod(_input: Node) -> Result<()> {
+//!         Ok(())
+//!     }
+//! }
+//! # fn main() {}
+//! ```
+//!
+//! # Trivial methods
+//!
+//! Methods for leaf rules often just convert the matched string. Such methods can be declared
+//! without a body, with an attribute that says how to convert it: `#[from_str]` parses the string
+//! with [`str::parse`], and `#[as_str]` returns it (or anything it can be converted `into`).
+//!
+//! ```ignore
+//! #[pest_consume::parser]
+//! impl CSVParser {
+//!     #[from_str]
+//!     fn field(input: Node) -> Result<f64>;
+//!     #[as_str]
+//!     fn name(input: Node) -> Result<&str>;
+//!     ...
+//! }
+//! ```
+//!
+//! Likewise, when there is no `EOI` method, one that returns `()` is generated.
+//!
+//! # Deriving types from nodes
+//!
+//! Types that mirror the grammar can be built from nodes without writing a consumer method, by
+//! deriving [`FromNode`]. A method declared with `#[from_node]` and no body then makes them
+//! available to [`match_nodes!`] like any other rule.
+//!
+//! ```ignore
+//! #[derive(pest_consume::FromNode)]
+//! #[rule(record)]
+//! struct Record {
+//!     #[rule(field)]
+//!     fields: Vec<f64>,
+//! }
+//!
+//! #[pest_consume::parser]
+//! impl CSVParser {
+//!     #[from_node]
+//!     fn record(input: Node) -> Result<Record>;
+//!     ...
+//! }
+//! ```
+//!
+//! For a typed view of the parse tree without writing any consumer, see [`cst!`].
+//!
 //! # Examples
 //!
 //! Some toy examples can be found in [the `examples/` directory][examples].
@@ -134,17 +260,13 @@
 //!
 //! # Advanced features
 //!
-//! See [here][advanced_features] for precedence climbing, passing custom data through the parser, and more.
+//! See [here][advanced_features] for precedence climbing, passing custom data through the parser, custom error types, collecting several errors, and more.
 //!
 //! # Compatibility
 //!
-//! Works with rust >= 1.37.
-//!
-//! Needs rust >= 1.37 because it uses
-//! [this feature](https://blog.rust-lang.org/2019/08/15/Rust-1.37.0.html#referring-to-enum-variants-through-type-aliases).
-//! If there is demand for older versions of Rust, we might be able to work around that.
+//! Works with rust >= 1.61.
 //!
-//! Works with older nightly Rust, with `#![feature(type_alias_enum_variants)]`.
+//! Needs rust >= 1.61 because that is the minimum supported version of `pest` 2.7.
 //!
 //! # License
 //!
@@ -164,6 +286,8 @@
 //! [advanced_features]: advanced_features/index.html
 //! [`parser`]: macro@crate::parser
 //! [`Nodes`]: struct.Nodes.html
+//! [`FromNode`]: trait.FromNode.html
+//! [`cst!`]: macro.cst.html
 //! [`Node`]: struct.Node.html
 //! [`Node::as_str`]: struct.Node.html#method.as_str
 //! [`Parser`]: trait.Parser.html
@@ -172,6 +296,8 @@
 //! [examples]: https://github.com/Nadrieril/pest_consume/tree/master/pest_consume/examples
 //! [dhall-rust-parser]: https://github.com/Nadrieril/dhall-rust/blob/4daead27eb65e3a38869924f0f3ed1f425de1b33/dhall_syntax/src/parser.rs
 
+#[doc(hidden)]
+pub use pest;
 pub use pest::error::Error;
 pub use pest_derive::Parser;
 
@@ -179,10 +305,24 @@ mod match_nodes;
 #[doc(hidden)]
 pub use match_nodes::*;
 
+mod cst;
+#[doc(hidden)]
+pub use cst::*;
+
 pub mod advanced_features;
 
+mod context;
+mod diagnostic;
+mod from_node;
 mod node;
+mod parse_error;
 mod parser;
+pub use diagnostic::Diagnostic;
+pub use from_node::FromNode;
 pub use node::{Node, Nodes};
+pub use parse_error::ParseError;
 pub use parser::Parser;
+#[doc(hidden)]
+pub use parser::{ByMutRef, ByRef, ConsumeFn};
 pub use pest_consume_macros::parser;
+pub use pest_consume_macros::FromNode;
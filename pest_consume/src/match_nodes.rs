@@ -16,7 +16,14 @@
 /// The macro takes an expression followed by `;`, followed by one or more branches separated by `,`.
 /// Each branch has the form `[$patterns] => $body`. The body is an arbitrary expression.
 /// The patterns are a comma-seperated list of either `$rule_name($binder)` or just `$binder`, each
-/// optionally followed by `..` to indicate a variable-length pattern.
+/// optionally followed by `..` to indicate a variable-length pattern (or `..lazy` for a lazily
+/// parsed one, or `..recover` for one that skips failing nodes), or by `?` to indicate an optional
+/// pattern. Several rules can be given for the same item, as
+/// `$rule_name1($binder) | $rule_name2($binder)`. A binder can itself be a bracketed pattern, to
+/// match on the children of a node. Extra arguments for the method can be given after the binder,
+/// as `$rule_name($binder with $args)`.
+/// A branch can also have guards, as `[$patterns] where $raw_guard if $guard => $body`; both are
+/// optional.
 ///
 /// # How it works
 ///
@@ -32,12 +39,13 @@
 /// desugars roughly into:
 /// ```ignore
 /// let nodes = { input.into_children() };
-/// if ... { // check that all rules in `nodes` are the `field` rule
+/// if let Some(counts) = ... { // check that all rules in `nodes` are the `field` rule
 ///     let fields = nodes
+///         .take(counts[0])
 ///         .map(|node| Self::field(node)) // Recursively parse children nodes
 ///         ... // Propagate errors
 ///     { fields.count() }
-/// } else if ... { // check that the nodes has two elements, with rules `string` and `number`
+/// } else if let Some(counts) = ... { // check that the nodes has two elements, with rules `string` and `number`
 ///     let s = Self::string(nodes.next().unwrap())?;
 ///     let n = Self::number(nodes.next().unwrap())?;
 ///     { s.len() + n }
@@ -46,6 +54,163 @@
 /// }
 /// ```
 ///
+/// To decide which nodes each item of a pattern matches, variable-length and optional items match
+/// as many nodes as they can, from left to right, while still allowing the rest of the pattern to
+/// match.
+///
+/// # Several variable-length patterns
+///
+/// A pattern can contain several `..` items, as long as the rules make it clear where each of them
+/// stops:
+/// ```ignore
+/// // file = { header* ~ body ~ footer* }
+/// match_nodes!(input.into_children();
+///     [header(hs).., body(b), footer(fs)..] => { ... },
+/// )
+/// // module = { import* ~ decl* }
+/// match_nodes!(input.into_children();
+///     [import(is).., decl(ds)..] => { ... },
+/// )
+/// ```
+/// A pattern where a variable-length or optional item could swallow the nodes meant for a
+/// following one, e.g. `[xs.., ys..]` or `[import(xs).., import(ys)..]`, is rejected at compile
+/// time:
+/// ```compile_fail
+/// # use pest_consume::{match_nodes, Error, Parser};
+/// # type Result<T> = std::result::Result<T, Error<Rule>>;
+/// # type Node<'i> = pest_consume::Node<'i, Rule, ()>;
+/// # #[derive(Parser)]
+/// # #[grammar_inline = "number = { ASCII_DIGIT } list = { number* }"]
+/// # struct ListParser;
+/// # #[pest_consume::parser]
+/// # impl ListParser {
+/// #     fn number(input: Node) -> Result<u8> {
+/// #         input.as_str().parse().map_err(|e| input.error(e))
+/// #     }
+/// // error: ambiguous pattern
+/// fn list(input: Node) -> Result<(Vec<u8>, Vec<u8>)> {
+///     Ok(match_nodes!(input.into_children();
+///         [number(xs).., number(ys)..] => (xs.collect(), ys.collect()),
+///     ))
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// # Lazy variable-length patterns
+///
+/// A `..` item parses all its nodes before the body of the branch runs, and stores the results.
+/// For long lists of nodes, `..lazy` can be used instead: it binds an iterator that parses each
+/// node when it is reached, and thus yields `Result`s.
+/// ```ignore
+/// // file = { record* }
+/// match_nodes!(input.into_children();
+///     [record(mut records)..lazy] => records.try_fold(0, |sum, r| r.map(|r| sum + r.len()))?,
+/// )
+/// ```
+/// Errors are then only reported if the body consumes the iterator far enough.
+/// `..lazy` is not supported on nested patterns.
+///
+/// # Recovering from errors in variable-length patterns
+///
+/// With `..recover`, a node that fails to parse doesn't stop the parsing of the others: its error
+/// is reported with [`Nodes::report_error`], and the item binds an iterator over the values of
+/// the other nodes.
+/// ```ignore
+/// // file = { record* }
+/// match_nodes!(input.into_children();
+///     [record(records)..recover] => records.collect(),
+/// )
+/// ```
+/// This is meant for parsing with [`Parser::parse_collecting_errors`], which collects the
+/// reported errors. Otherwise the first error is returned, as with `..`. In a branch with an `if`
+/// guard, the errors are only reported once the guard holds.
+///
+/// # Optional patterns
+///
+/// An item followed by `?` matches zero or one node, and binds an `Option` with the result.
+/// Several optional items can appear in the same pattern:
+/// ```ignore
+/// // fn_decl = { name ~ type_params? ~ args ~ ret_type? }
+/// match_nodes!(input.into_children();
+///     [name(n), type_params(tps)?, args(args), ret_type(ret)?] => { ... },
+/// )
+/// ```
+///
+/// # Alternative rules
+///
+/// An item can accept nodes of several rules, as long as the corresponding methods return the same
+/// type. Each node is then parsed with the method of its own rule.
+/// ```ignore
+/// // expr = { lit | ident | call }
+/// match_nodes!(input.into_children();
+///     [lit(x) | ident(x) | call(x)] => x,
+/// )
+/// // args = { (lit | ident)* }
+/// match_nodes!(input.into_children();
+///     [lit(xs) | ident(xs)..] => xs.collect(),
+/// )
+/// ```
+/// The binder must be the same for all the alternatives.
+///
+/// # Passing extra arguments
+///
+/// Consumer methods can take more arguments after the `Node`, for information that depends on
+/// where the rule is used, like an enclosing scope or an expected type. The values to pass are
+/// given after `with`:
+/// ```ignore
+/// fn expr(input: Node, scope: &Scope, expected: Type) -> Result<Expr> { ... }
+///
+/// match_nodes!(input.into_children();
+///     [expr(e with &scope, Type::Int)] => e,
+///     [expr(es with &scope, Type::Int)..] => es.collect(),
+/// )
+/// ```
+/// The arguments are evaluated for each node parsed, so `..` items usually need to pass
+/// references or `Copy` values. Methods that share an alias must take the same extra arguments,
+/// which are forwarded when the aliased method is called.
+///
+/// # Nested patterns
+///
+/// Instead of a binder, an item can contain a pattern in brackets. It is matched against the
+/// children of the node, with the same syntax and semantics as the top-level pattern. This avoids
+/// writing methods for trivial wrapper rules:
+/// ```ignore
+/// // pair = { key ~ "=" ~ value }
+/// // map = { pair* }
+/// match_nodes!(input.into_children();
+///     [pair([key(ks), value(vs)])..] => ks.zip(vs).collect(),
+/// )
+/// ```
+/// Here `pair` doesn't need a method: the rule of a node matched by a nested pattern is compared
+/// directly, without going through [rule aliasing]. Each variable of the nested pattern is bound
+/// as the result of a method would be: as-is for a single item, as an `Option` for a `?` item, and
+/// as an iterator for a `..` item. In the example above, `ks` and `vs` are iterators.
+///
+/// # Guards
+///
+/// Like in a `match` expression, a branch can have an `if` guard. It is evaluated after the nodes
+/// have been parsed, and can use the bound variables. If it is false, the next branches are tried
+/// on the same nodes.
+/// ```ignore
+/// match_nodes!(input.into_children();
+///     [ident(name)] if name == "self" => { ... },
+///     [ident(name)] => { ... },
+/// )
+/// ```
+///
+/// Since the nodes are parsed before evaluating an `if` guard, any error while parsing them is
+/// returned even if the guard would have been false. To decide before parsing anything, use a
+/// `where` guard instead. It is evaluated on the raw nodes: each binder that is a plain variable
+/// is bound to the `Node` that the item would match, to an `Option<Node>` for a `?` item, or to a
+/// `Vec<Node>` for a `..` item.
+/// ```ignore
+/// match_nodes!(input.into_children();
+///     [ident(name)] where name.as_str() == "self" => { ... },
+///     [ident(name)] => { ... },
+/// )
+/// ```
+///
 /// # Matching raw nodes
 ///
 /// Sometimes you may want to manipulate `Node`s directly. For that, just omit a rule name when
@@ -73,12 +238,24 @@
 ///     ...
 /// )
 /// ```
+/// In a parser declared with `#[pest_consume::parser(stateful)]`, the methods must be called on
+/// `self` instead, which is passed first: `match_nodes!(self; nodes; ...)`. Outside a method, pass
+/// the parser instance the same way, followed by its type:
+/// `match_nodes!(parser; <CSVParser>; nodes; ...)`.
 ///
 /// It also assumes it can `return Err(...)` in case of errors.
 ///
+/// When no branch matches, the error lists the patterns that were expected and the rules that
+/// were found, e.g. ``expected one of `[number]`, `[string]`, found `[field, field]` ``. It points
+/// at the node where the pattern that went the furthest stopped matching. If a pattern matched but
+/// its guard didn't hold, the error says so and points at all the nodes.
+///
 /// [`pest_consume`]: index.html
 /// [advanced features]: advanced_features/index.html
+/// [rule aliasing]: advanced_features/rule_aliasing/index.html
 /// [`Nodes`]: struct.Nodes.html
+/// [`Nodes::report_error`]: struct.Nodes.html#method.report_error
+/// [`Parser::parse_collecting_errors`]: trait.Parser.html#method.parse_collecting_errors
 /// [examples]: https://github.com/Nadrieril/pest_consume/tree/master/pest_consume/examples
 // We wrap the proc-macro in a macro here because I want to write the doc in this crate.
 #[macro_export]
@@ -88,3 +265,69 @@ macro_rules! match_nodes {
     };
 }
 pub use pest_consume_macros::match_nodes as match_nodes_;
+
+/// How many nodes an item of a `match_nodes!` pattern can match.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplicity {
+    /// Exactly one node: `rule(x)`.
+    Single,
+    /// Zero or one node: `rule(x)?`.
+    Optional,
+    /// Any number of nodes: `rule(x)..`.
+    Multiple,
+}
+
+/// Finds how to split a list of `len` nodes among the items of a `match_nodes!` pattern.
+/// Each item is given with a predicate that tells whether it can match the node at a given index.
+/// Returns the number of nodes matched by each item. If the pattern doesn't match, returns
+/// instead the index of the first node that couldn't be matched in the attempt that went the
+/// furthest, which is `len` if nodes were missing.
+///
+/// Items match greedily, from left to right, and backtrack when the rest of the pattern fails.
+#[doc(hidden)]
+pub fn match_pattern<const N: usize>(
+    items: &[(Multiplicity, &dyn Fn(usize) -> bool); N],
+    len: usize,
+) -> Result<[usize; N], usize> {
+    fn go(
+        items: &[(Multiplicity, &dyn Fn(usize) -> bool)],
+        pos: usize,
+        len: usize,
+        counts: &mut [usize],
+        furthest: &mut usize,
+    ) -> bool {
+        let (multiplicity, matches) = match items.first() {
+            Some(item) => item,
+            None => {
+                *furthest = (*furthest).max(pos);
+                return pos == len;
+            }
+        };
+        let (min, max) = match multiplicity {
+            Multiplicity::Single => (1, 1),
+            Multiplicity::Optional => (0, 1),
+            Multiplicity::Multiple => (0, len - pos),
+        };
+        let mut available = 0;
+        while available < max
+            && pos + available < len
+            && matches(pos + available)
+        {
+            available += 1;
+        }
+        *furthest = (*furthest).max(pos + available);
+        (min..=available).rev().any(|count| {
+            counts[0] = count;
+            go(&items[1..], pos + count, len, &mut counts[1..], furthest)
+        })
+    }
+
+    let mut counts = [0; N];
+    let mut furthest = 0;
+    if go(items, 0, len, &mut counts, &mut furthest) {
+        Ok(counts)
+    } else {
+        Err(furthest)
+    }
+}
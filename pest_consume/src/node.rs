@@ -1,15 +1,32 @@
-use crate::Parser;
-use pest::error::{Error, ErrorVariant};
+use std::borrow::Borrow;
+use std::cell::{RefCell, RefMut};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use crate::context::Context;
+use crate::{Diagnostic, ParseError, Parser};
+use pest::error::Error;
 use pest::iterators::{Pair, Pairs};
+use pest::pratt_parser::PrattParser;
+#[allow(deprecated)]
 use pest::prec_climber::PrecClimber;
 use pest::Parser as PestParser;
 use pest::{RuleType, Span};
 
 /// A node of the parse tree.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Node<'input, Rule: RuleType, Data> {
+///
+/// `E` is the type of the errors created from this node; see [`ParseError`].
+///
+/// [`ParseError`]: trait.ParseError.html
+pub struct Node<'input, Rule: RuleType, Data, E = Error<Rule>> {
     pair: Pair<'input, Rule>,
-    user_data: Data,
+    /// Shared by all the nodes of a parse, so that it doesn't need to be cloned.
+    user_data: Rc<Data>,
+    context: Rc<Context<Rule, E>>,
+    /// How many ancestors the node has, which tells apart nested nodes with the same rule and
+    /// span.
+    depth: usize,
 }
 
 /// Iterator over [`Node`]s. It is created by [`Node::children`] or [`Parser::parse`].
@@ -17,26 +34,43 @@ pub struct Node<'input, Rule: RuleType, Data> {
 /// [`Node`]: struct.Node.html
 /// [`Node::children`]: struct.Node.html#method.children
 /// [`Parser::parse`]: trait.Parser.html#method.parse
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Nodes<'input, Rule: RuleType, Data> {
+pub struct Nodes<'input, Rule: RuleType, Data, E = Error<Rule>> {
     pairs: Pairs<'input, Rule>,
     span: Span<'input>,
-    user_data: Data,
+    user_data: Rc<Data>,
+    context: Rc<Context<Rule, E>>,
+    /// The depth of the nodes in the iterator.
+    depth: usize,
 }
 
-impl<'i, R: RuleType> Node<'i, R, ()> {
+impl<'i, R: RuleType, E> Node<'i, R, (), E> {
     #[doc(hidden)]
     pub fn new(pair: Pair<'i, R>) -> Self {
-        Node {
-            pair,
-            user_data: (),
-        }
+        Node::new_with_user_data(pair, ())
     }
 }
-impl<'i, R: RuleType, D> Node<'i, R, D> {
+impl<'i, R: RuleType, D, E> Node<'i, R, D, E> {
     #[doc(hidden)]
     pub fn new_with_user_data(pair: Pair<'i, R>, user_data: D) -> Self {
-        Node { pair, user_data }
+        Node::new_with_context(
+            pair,
+            Rc::new(user_data),
+            Rc::new(Context::new()),
+            0,
+        )
+    }
+    fn new_with_context(
+        pair: Pair<'i, R>,
+        user_data: Rc<D>,
+        context: Rc<Context<R, E>>,
+        depth: usize,
+    ) -> Self {
+        Node {
+            pair,
+            user_data,
+            context,
+            depth,
+        }
     }
     pub fn as_str(&self) -> &'i str {
         self.pair.as_str()
@@ -48,46 +82,107 @@ impl<'i, R: RuleType, D> Node<'i, R, D> {
         self.pair.as_rule()
     }
     #[doc(hidden)]
-    pub fn as_aliased_rule<C>(&self) -> C::AliasedRule
+    pub fn as_aliased_rule<C>(&self) -> Option<C::AliasedRule>
     where
         C: Parser<Rule = R>,
         <C as Parser>::Parser: PestParser<R>,
     {
         C::rule_alias(self.as_rule())
     }
+    /// Consumes the node with `consume`, unless a node with the same rule, span and depth was
+    /// already consumed during this parse, in which case the previous result is returned.
+    #[doc(hidden)]
+    pub fn memoize<T, F>(self, consume: F) -> Result<T, E>
+    where
+        T: Clone + 'static,
+        F: FnOnce(Self) -> Result<T, E>,
+    {
+        let span = self.as_span();
+        let key = (self.as_rule(), span.start(), span.end(), self.depth);
+        if let Some(value) = self.context.memoized(key) {
+            return Ok(value);
+        }
+        let context = self.context.clone();
+        let value = consume(self)?;
+        context.memoize(key, value.clone());
+        Ok(value)
+    }
+    /// The name of `rule` in error messages, taking display names into account.
+    #[doc(hidden)]
+    pub fn rule_name(&self, rule: R) -> String {
+        self.context.rule_name(rule)
+    }
 
     /// Returns an iterator over the children of this node
-    pub fn into_children(self) -> Nodes<'i, R, D> {
+    pub fn into_children(self) -> Nodes<'i, R, D, E> {
         let span = self.as_span();
         Nodes {
             pairs: self.pair.into_inner(),
             span,
             user_data: self.user_data,
+            context: self.context,
+            depth: self.depth + 1,
         }
     }
     /// Returns an iterator over the children of this node
-    pub fn children(&self) -> Nodes<'i, R, D>
-    where
-        D: Clone,
-    {
+    pub fn children(&self) -> Nodes<'i, R, D, E> {
         self.clone().into_children()
     }
 
     /// Create an error that points to the span of the node.
-    pub fn error<S: ToString>(&self, message: S) -> Error<R> {
-        Error::new_from_span(
-            ErrorVariant::CustomError {
-                message: message.to_string(),
-            },
-            self.as_span(),
-        )
+    pub fn error<S: ToString>(&self, message: S) -> E
+    where
+        E: ParseError<R>,
+    {
+        self.context.error(self.as_span(), message.to_string())
+    }
+    /// Create a [`Diagnostic`] that points to the span of the node, to which labels, notes
+    /// and help messages can be added.
+    ///
+    /// [`Diagnostic`]: struct.Diagnostic.html
+    pub fn diagnostic<S: ToString>(&self, message: S) -> Diagnostic<'i> {
+        self.context.diagnostic(self.as_span(), message.to_string())
+    }
+    /// Reports an error that shouldn't stop parsing. When errors are being collected, as with
+    /// [`Parser::parse_collecting_errors`], the error is recorded and `Ok(())` is returned, so
+    /// that the method can carry on, for example by returning a placeholder value. Otherwise
+    /// the error is returned, to be propagated like any other error.
+    ///
+    /// ```ignore
+    /// fn field(input: Node) -> Result<f64> {
+    ///     match input.as_str().parse::<f64>() {
+    ///         Ok(x) => Ok(x),
+    ///         Err(e) => {
+    ///             input.report_error(input.error(e))?;
+    ///             Ok(f64::NAN)
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [`Parser::parse_collecting_errors`]: trait.Parser.html#method.parse_collecting_errors
+    pub fn report_error(&self, error: E) -> Result<(), E> {
+        self.context.report_error(error)
     }
 
     pub fn user_data(&self) -> &D {
         &self.user_data
     }
-    pub fn into_user_data(self) -> D {
-        self.user_data
+    /// Returns the user data, which is cloned unless this is the last node that refers to it.
+    pub fn into_user_data(self) -> D
+    where
+        D: Clone,
+    {
+        Rc::try_unwrap(self.user_data).unwrap_or_else(|data| (*data).clone())
+    }
+    /// Mutably borrows the user data, when it is a `RefCell` or a reference to one. Since the data
+    /// is shared by all the nodes, this panics if it is already borrowed, e.g. by a parent node
+    /// that holds the borrow while parsing its children.
+    pub fn user_data_mut<T>(&self) -> RefMut<'_, T>
+    where
+        D: Borrow<RefCell<T>>,
+    {
+        (*self.user_data).borrow().borrow_mut()
     }
     pub fn as_pair(&self) -> &Pair<'i, R> {
         &self.pair
@@ -97,53 +192,71 @@ impl<'i, R: RuleType, D> Node<'i, R, D> {
     }
 }
 
-impl<'i, R: RuleType, D> Nodes<'i, R, D> {
+impl<'i, R: RuleType, D, E> Nodes<'i, R, D, E> {
     /// `input` must be the _original_ input that `pairs` is pointing to.
     #[doc(hidden)]
     pub(crate) fn new(
         input: &'i str,
         pairs: Pairs<'i, R>,
-        user_data: D,
+        user_data: Rc<D>,
+        context: Rc<Context<R, E>>,
     ) -> Self {
         let span = Span::new(input, 0, input.len()).unwrap();
         Nodes {
             pairs,
             span,
             user_data,
+            context,
+            depth: 0,
         }
     }
     /// Create an error that points to the initial span of the nodes.
     /// Note that this span does not change as the iterator is consumed.
-    pub fn error<S: ToString>(&self, message: S) -> Error<R> {
-        Error::new_from_span(
-            ErrorVariant::CustomError {
-                message: message.to_string(),
-            },
-            self.span.clone(),
-        )
+    pub fn error<S: ToString>(&self, message: S) -> E
+    where
+        E: ParseError<R>,
+    {
+        self.context.error(self.span, message.to_string())
+    }
+    /// Create a [`Diagnostic`] that points to the initial span of the nodes.
+    ///
+    /// [`Diagnostic`]: struct.Diagnostic.html
+    pub fn diagnostic<S: ToString>(&self, message: S) -> Diagnostic<'i> {
+        self.context.diagnostic(self.span, message.to_string())
+    }
+    /// Reports an error that shouldn't stop parsing. See [`Node::report_error`].
+    ///
+    /// [`Node::report_error`]: struct.Node.html#method.report_error
+    pub fn report_error(&self, error: E) -> Result<(), E> {
+        self.context.report_error(error)
     }
     /// Returns the only element if there is only one element.
-    pub fn single(mut self) -> Result<Node<'i, R, D>, Error<R>> {
+    pub fn single(mut self) -> Result<Node<'i, R, D, E>, E>
+    where
+        E: ParseError<R>,
+    {
         match (self.pairs.next(), self.pairs.next()) {
-            (Some(pair), None) => {
-                Ok(Node::new_with_user_data(pair, self.user_data))
-            }
+            (Some(pair), None) => Ok(Node::new_with_context(
+                pair,
+                self.user_data,
+                self.context,
+                self.depth,
+            )),
             (first, second) => {
+                let context = self.context;
                 let node_rules: Vec<_> = first
                     .into_iter()
                     .chain(second)
                     .chain(self.pairs)
-                    .map(|p| p.as_rule())
+                    .map(|p| context.rule_name(p.as_rule()))
                     .collect();
 
-                Err(Error::new_from_span(
-                    ErrorVariant::CustomError {
-                        message: format!(
-                            "Expected a single node, instead got: {:?}",
-                            node_rules
-                        ),
-                    },
+                Err(context.error(
                     self.span,
+                    format!(
+                        "Expected a single node, instead got: [{}]",
+                        node_rules.join(", ")
+                    ),
                 ))
             }
         }
@@ -153,7 +266,7 @@ impl<'i, R: RuleType, D> Nodes<'i, R, D> {
         &self,
     ) -> std::iter::Map<
         Pairs<'i, R>,
-        impl FnMut(Pair<'i, R>) -> <C as Parser>::AliasedRule,
+        impl FnMut(Pair<'i, R>) -> Option<<C as Parser>::AliasedRule>,
     >
     where
         C: Parser<Rule = R>,
@@ -162,38 +275,86 @@ impl<'i, R: RuleType, D> Nodes<'i, R, D> {
         self.pairs.clone().map(|p| C::rule_alias(p.as_rule()))
     }
     /// Construct a node with the provided pair, passing the user data along.
-    fn with_pair(&self, pair: Pair<'i, R>) -> Node<'i, R, D>
-    where
-        D: Clone,
-    {
-        Node::new_with_user_data(pair, self.user_data.clone())
+    fn with_pair(&self, pair: Pair<'i, R>) -> Node<'i, R, D, E> {
+        Node::new_with_context(
+            pair,
+            self.user_data.clone(),
+            self.context.clone(),
+            self.depth,
+        )
     }
     /// Performs the precedence climbing algorithm on the nodes.
-    pub fn prec_climb<T, E, F1, F2>(
+    #[allow(deprecated)]
+    pub fn prec_climb<T, F1, F2>(
         self,
         climber: &PrecClimber<R>,
         mut primary: F1,
         mut infix: F2,
     ) -> Result<T, E>
     where
-        D: Clone,
-        F1: FnMut(Node<'i, R, D>) -> Result<T, E>,
-        F2: FnMut(T, Node<'i, R, D>, T) -> Result<T, E>,
+        F1: FnMut(Node<'i, R, D, E>) -> Result<T, E>,
+        F2: FnMut(T, Node<'i, R, D, E>, T) -> Result<T, E>,
     {
-        let user_data = self.user_data;
-        let with_pair = |p| Node::new_with_user_data(p, user_data.clone());
+        let (user_data, context, depth) =
+            (self.user_data, self.context, self.depth);
+        let with_pair = |p| {
+            Node::new_with_context(p, user_data.clone(), context.clone(), depth)
+        };
         climber.climb(
             self.pairs,
             |p| primary(with_pair(p)),
             |l, p, r| infix(l?, with_pair(p), r?),
         )
     }
+    /// Performs Pratt parsing on the nodes. Unlike [`prec_climb`](#method.prec_climb), this
+    /// supports prefix and postfix operators in addition to infix ones.
+    pub fn pratt<T, F1, F2, F3, F4>(
+        self,
+        pratt: &PrattParser<R>,
+        mut primary: F1,
+        mut prefix: F2,
+        mut infix: F3,
+        mut postfix: F4,
+    ) -> Result<T, E>
+    where
+        F1: FnMut(Node<'i, R, D, E>) -> Result<T, E>,
+        F2: FnMut(Node<'i, R, D, E>, T) -> Result<T, E>,
+        F3: FnMut(T, Node<'i, R, D, E>, T) -> Result<T, E>,
+        F4: FnMut(T, Node<'i, R, D, E>) -> Result<T, E>,
+    {
+        let (user_data, context, depth) =
+            (self.user_data, self.context, self.depth);
+        let with_pair = |p| {
+            Node::new_with_context(p, user_data.clone(), context.clone(), depth)
+        };
+        let with_pair = &with_pair;
+        let result = pratt
+            .map_primary(|p| primary(with_pair(p)))
+            .map_prefix(move |p, r| prefix(with_pair(p), r?))
+            .map_infix(move |l, p, r| infix(l?, with_pair(p), r?))
+            .map_postfix(move |l, p| postfix(l?, with_pair(p)))
+            .parse(self.pairs);
+        result
+    }
 
     pub fn user_data(&self) -> &D {
         &self.user_data
     }
-    pub fn into_user_data(self) -> D {
-        self.user_data
+    /// Returns the user data, which is cloned unless this is the last node that refers to it.
+    pub fn into_user_data(self) -> D
+    where
+        D: Clone,
+    {
+        Rc::try_unwrap(self.user_data).unwrap_or_else(|data| (*data).clone())
+    }
+    /// Mutably borrows the user data, when it is a `RefCell` or a reference to one. Since the data
+    /// is shared by all the nodes, this panics if it is already borrowed, e.g. by a parent node
+    /// that holds the borrow while parsing its children.
+    pub fn user_data_mut<T>(&self) -> RefMut<'_, T>
+    where
+        D: Borrow<RefCell<T>>,
+    {
+        (*self.user_data).borrow().borrow_mut()
     }
     pub fn as_pairs(&self) -> &Pairs<'i, R> {
         &self.pairs
@@ -203,12 +364,11 @@ impl<'i, R: RuleType, D> Nodes<'i, R, D> {
     }
 }
 
-impl<'i, R, D> Iterator for Nodes<'i, R, D>
+impl<'i, R, D, E> Iterator for Nodes<'i, R, D, E>
 where
     R: RuleType,
-    D: Clone,
 {
-    type Item = Node<'i, R, D>;
+    type Item = Node<'i, R, D, E>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let child_pair = self.pairs.next()?;
@@ -217,10 +377,9 @@ where
     }
 }
 
-impl<'i, R, D> DoubleEndedIterator for Nodes<'i, R, D>
+impl<'i, R, D, E> DoubleEndedIterator for Nodes<'i, R, D, E>
 where
     R: RuleType,
-    D: Clone,
 {
     fn next_back(&mut self) -> Option<Self::Item> {
         let child_pair = self.pairs.next_back()?;
@@ -229,14 +388,92 @@ where
     }
 }
 
-impl<'i, R: RuleType, D> std::fmt::Display for Node<'i, R, D> {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl<'i, R: RuleType, D, E> fmt::Display for Node<'i, R, D, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.pair.fmt(f)
     }
 }
 
-impl<'i, R: RuleType, D> std::fmt::Display for Nodes<'i, R, D> {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl<'i, R: RuleType, D, E> fmt::Display for Nodes<'i, R, D, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.pairs.fmt(f)
     }
 }
+
+// The following are implemented by hand because deriving them would require the error type to
+// implement them too.
+
+impl<'i, R: RuleType, D: fmt::Debug, E> fmt::Debug for Node<'i, R, D, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("pair", &self.pair)
+            .field("user_data", &self.user_data)
+            .finish()
+    }
+}
+
+impl<'i, R: RuleType, D: fmt::Debug, E> fmt::Debug for Nodes<'i, R, D, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Nodes")
+            .field("pairs", &self.pairs)
+            .field("span", &self.span)
+            .field("user_data", &self.user_data)
+            .finish()
+    }
+}
+
+impl<'i, R: RuleType, D, E> Clone for Node<'i, R, D, E> {
+    fn clone(&self) -> Self {
+        Node::new_with_context(
+            self.pair.clone(),
+            self.user_data.clone(),
+            self.context.clone(),
+            self.depth,
+        )
+    }
+}
+
+impl<'i, R: RuleType, D, E> Clone for Nodes<'i, R, D, E> {
+    fn clone(&self) -> Self {
+        Nodes {
+            pairs: self.pairs.clone(),
+            span: self.span,
+            user_data: self.user_data.clone(),
+            context: self.context.clone(),
+            depth: self.depth,
+        }
+    }
+}
+
+impl<'i, R: RuleType, D: PartialEq, E> PartialEq for Node<'i, R, D, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.pair == other.pair && self.user_data == other.user_data
+    }
+}
+
+impl<'i, R: RuleType, D: PartialEq, E> PartialEq for Nodes<'i, R, D, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.pairs == other.pairs
+            && self.span == other.span
+            && self.user_data == other.user_data
+    }
+}
+
+impl<'i, R: RuleType, D: Eq, E> Eq for Node<'i, R, D, E> {}
+
+impl<'i, R: RuleType, D: Eq, E> Eq for Nodes<'i, R, D, E> {}
+
+impl<'i, R: RuleType, D: Hash, E> Hash for Node<'i, R, D, E> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.pair.hash(state);
+        self.user_data.hash(state);
+    }
+}
+
+impl<'i, R: RuleType, D: Hash, E> Hash for Nodes<'i, R, D, E> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.pairs.hash(state);
+        self.span.hash(state);
+        self.user_data.hash(state);
+    }
+}
@@ -0,0 +1,54 @@
+use pest::error::{Error, ErrorVariant};
+use pest::{RuleType, Span};
+
+use crate::Diagnostic;
+
+/// The errors that consumer methods can return.
+///
+/// This is implemented for [`pest::error::Error`], which is what parsers use by default.
+/// See [here][custom_errors] for how to use your own error type.
+///
+/// [`pest::error::Error`]: https://docs.rs/pest/2/pest/error/struct.Error.html
+/// [custom_errors]: advanced_features/custom_errors/index.html
+pub trait ParseError<R: RuleType>: Sized {
+    /// Converts an error reported by pest, e.g. because the input doesn't match the grammar.
+    fn from_pest_error(error: Error<R>) -> Self;
+
+    /// Creates an error that points to `span`.
+    fn from_span(span: Span<'_>, message: String) -> Self {
+        Self::from_pest_error(Error::new_from_span(
+            ErrorVariant::CustomError { message },
+            span,
+        ))
+    }
+
+    /// Converts a [`Diagnostic`]. By default, this only keeps its primary span, and includes
+    /// the rest in the message.
+    ///
+    /// [`Diagnostic`]: struct.Diagnostic.html
+    fn from_diagnostic(diagnostic: Diagnostic<'_>) -> Self {
+        let error =
+            Self::from_span(diagnostic.span(), diagnostic.flattened_message());
+        match diagnostic.source_name() {
+            Some(name) => error.with_source_name(name),
+            None => error,
+        }
+    }
+
+    /// Records the name of the input the error comes from, e.g. its path, when parsing with
+    /// [`Parser::parse_named`]. Does nothing by default.
+    ///
+    /// [`Parser::parse_named`]: trait.Parser.html#method.parse_named
+    fn with_source_name(self, _name: &str) -> Self {
+        self
+    }
+}
+
+impl<R: RuleType> ParseError<R> for Error<R> {
+    fn from_pest_error(error: Error<R>) -> Self {
+        error
+    }
+    fn with_source_name(self, name: &str) -> Self {
+        self.with_path(name)
+    }
+}
@@ -1,4 +1,9 @@
-use crate::{Error, Nodes};
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::context::Context;
+use crate::{Node, Nodes, ParseError};
 use pest::Parser as PestParser;
 use pest::RuleType;
 
@@ -11,17 +16,38 @@ pub trait Parser {
     #[doc(hidden)]
     type AliasedRule: RuleType;
     type Parser: PestParser<Self::Rule>;
+    /// The type of errors returned by the parser. Defaults to [`Error`]; see [`ParseError`] to
+    /// use a custom one.
+    ///
+    /// [`Error`]: struct.Error.html
+    /// [`ParseError`]: trait.ParseError.html
+    type Error: ParseError<Self::Rule>;
 
+    /// Returns `None` if the rule has no corresponding method.
     #[doc(hidden)]
-    fn rule_alias(rule: Self::Rule) -> Self::AliasedRule;
+    fn rule_alias(rule: Self::Rule) -> Option<Self::AliasedRule>;
     #[doc(hidden)]
     fn allows_shortcut(rule: Self::Rule) -> bool;
+    /// The name of `rule` to use in the errors generated by this crate, as set with the
+    /// `#[display_name = "..."]` attribute on its method. Returns `None` if it doesn't have one,
+    /// in which case the name of the rule in the grammar is used.
+    fn rule_display_name(rule: Self::Rule) -> Option<&'static str> {
+        let _ = rule;
+        None
+    }
+    #[doc(hidden)]
+    fn aliased_rule_display_name(
+        rule: Self::AliasedRule,
+    ) -> Option<&'static str> {
+        let _ = rule;
+        None
+    }
 
     /// Parses a `&str` starting from `rule`
     fn parse<'i>(
         rule: Self::Rule,
         input_str: &'i str,
-    ) -> Result<Nodes<'i, Self::Rule, ()>, Error<Self::Rule>> {
+    ) -> Result<Nodes<'i, Self::Rule, (), Self::Error>, Self::Error> {
         Self::parse_with_userdata(rule, input_str, ())
     }
 
@@ -30,8 +56,231 @@ pub trait Parser {
         rule: Self::Rule,
         input_str: &'i str,
         user_data: D,
-    ) -> Result<Nodes<'i, Self::Rule, D>, Error<Self::Rule>> {
-        let pairs = Self::Parser::parse(rule, input_str)?;
-        Ok(Nodes::new(input_str, pairs, user_data))
+    ) -> Result<Nodes<'i, Self::Rule, D, Self::Error>, Self::Error> {
+        parse_in_context::<Self, D>(
+            rule,
+            input_str,
+            user_data,
+            Rc::new(
+                Context::new().with_rule_display_names(Self::rule_display_name),
+            ),
+        )
+    }
+
+    /// Parses a `&str` starting from `rule`, like [`parse`](#method.parse). The errors then
+    /// mention `name`, e.g. as `--> path/to/file.conf:1:5`.
+    fn parse_named<'i>(
+        rule: Self::Rule,
+        name: &str,
+        input_str: &'i str,
+    ) -> Result<Nodes<'i, Self::Rule, (), Self::Error>, Self::Error> {
+        Self::parse_named_with_userdata(rule, name, input_str, ())
+    }
+
+    /// Like [`parse_named`](#method.parse_named), carrying `user_data` through the parser
+    /// methods.
+    fn parse_named_with_userdata<'i, D>(
+        rule: Self::Rule,
+        name: &str,
+        input_str: &'i str,
+        user_data: D,
+    ) -> Result<Nodes<'i, Self::Rule, D, Self::Error>, Self::Error> {
+        let context = Context::new()
+            .with_source_name(name)
+            .with_rule_display_names(Self::rule_display_name);
+        parse_in_context::<Self, D>(
+            rule,
+            input_str,
+            user_data,
+            Rc::new(context),
+        )
+    }
+
+    /// Reads the file at `path`, parses it starting from `rule` and consumes the resulting node
+    /// with `consume`. The errors mention the path of the file, as with
+    /// [`parse_named`](#method.parse_named).
+    ///
+    /// ```ignore
+    /// let records = CSVParser::parse_file(Rule::file, "data.csv", CSVParser::file)??;
+    /// ```
+    fn parse_file<T, F>(
+        rule: Self::Rule,
+        path: impl AsRef<Path>,
+        consume: F,
+    ) -> io::Result<Result<T, Self::Error>>
+    where
+        F: for<'i> FnOnce(
+            Node<'i, Self::Rule, (), Self::Error>,
+        ) -> Result<T, Self::Error>,
+    {
+        let path = path.as_ref();
+        let input_str = std::fs::read_to_string(path)?;
+        let name = path.to_string_lossy();
+        Ok(Self::parse_named(rule, &name, &input_str)
+            .and_then(|nodes| nodes.single())
+            .and_then(consume))
+    }
+
+    /// Parses a `&str` starting from `rule`, and consumes the resulting node with `consume`,
+    /// which is given `self`. This is the entry point of parsers declared with
+    /// `#[pest_consume::parser(stateful)]`, whose methods take `&self` or `&mut self`; either
+    /// kind of method can be passed.
+    ///
+    /// ```ignore
+    /// let mut parser = CSVParser::default();
+    /// let records = parser.consume(Rule::file, input_str, CSVParser::file)?;
+    /// ```
+    fn consume<'i, T, M, F>(
+        &mut self,
+        rule: Self::Rule,
+        input_str: &'i str,
+        consume: F,
+    ) -> Result<T, Self::Error>
+    where
+        F: ConsumeFn<'i, Self, (), M, T>,
+    {
+        self.consume_with_userdata(rule, input_str, (), consume)
+    }
+
+    /// Like [`consume`](#method.consume), carrying `user_data` through the parser methods.
+    fn consume_with_userdata<'i, D, T, M, F>(
+        &mut self,
+        rule: Self::Rule,
+        input_str: &'i str,
+        user_data: D,
+        consume: F,
+    ) -> Result<T, Self::Error>
+    where
+        F: ConsumeFn<'i, Self, D, M, T>,
+    {
+        let node =
+            Self::parse_with_userdata(rule, input_str, user_data)?.single()?;
+        consume.call(self, node)
+    }
+
+    /// Parses a `&str` starting from `rule`, and consumes the resulting node with `consume`.
+    /// Unlike with [`parse`](#method.parse), errors reported with [`Node::report_error`] don't
+    /// stop parsing: they are all collected and returned along with the result. If parsing
+    /// fails anyway, the result is `None` and the error that stopped it comes last.
+    ///
+    /// ```ignore
+    /// let (file, errors) = CSVParser::parse_collecting_errors(
+    ///     Rule::file,
+    ///     input_str,
+    ///     CSVParser::file,
+    /// );
+    /// for e in errors {
+    ///     eprintln!("{}", e);
+    /// }
+    /// ```
+    ///
+    /// [`Node::report_error`]: struct.Node.html#method.report_error
+    fn parse_collecting_errors<'i, T, F>(
+        rule: Self::Rule,
+        input_str: &'i str,
+        consume: F,
+    ) -> (Option<T>, Vec<Self::Error>)
+    where
+        F: FnOnce(
+            Node<'i, Self::Rule, (), Self::Error>,
+        ) -> Result<T, Self::Error>,
+    {
+        Self::parse_with_userdata_collecting_errors(
+            rule,
+            input_str,
+            (),
+            consume,
+        )
+    }
+
+    /// Like [`parse_collecting_errors`](#method.parse_collecting_errors), carrying `user_data`
+    /// through the parser methods.
+    fn parse_with_userdata_collecting_errors<'i, D, T, F>(
+        rule: Self::Rule,
+        input_str: &'i str,
+        user_data: D,
+        consume: F,
+    ) -> (Option<T>, Vec<Self::Error>)
+    where
+        F: FnOnce(
+            Node<'i, Self::Rule, D, Self::Error>,
+        ) -> Result<T, Self::Error>,
+    {
+        let context = Rc::new(
+            Context::new()
+                .collecting_errors()
+                .with_rule_display_names(Self::rule_display_name),
+        );
+        let result = parse_in_context::<Self, D>(
+            rule,
+            input_str,
+            user_data,
+            context.clone(),
+        )
+        .and_then(|nodes| nodes.single())
+        .and_then(consume);
+        let mut errors = context.take_errors();
+        match result {
+            Ok(value) => (Some(value), errors),
+            Err(e) => {
+                errors.push(e);
+                (None, errors)
+            }
+        }
+    }
+}
+
+fn parse_in_context<'i, P: Parser + ?Sized, D>(
+    rule: P::Rule,
+    input_str: &'i str,
+    user_data: D,
+    context: Rc<Context<P::Rule, P::Error>>,
+) -> Result<Nodes<'i, P::Rule, D, P::Error>, P::Error> {
+    let pairs =
+        P::Parser::parse(rule, input_str).map_err(|e| context.pest_error(e))?;
+    Ok(Nodes::new(input_str, pairs, Rc::new(user_data), context))
+}
+
+/// A function that consumes a node given the parser, by `&mut` or by `&` reference. `M` tells the
+/// two apart.
+#[doc(hidden)]
+pub trait ConsumeFn<'i, P: Parser + ?Sized, D, M, T> {
+    fn call(
+        self,
+        parser: &mut P,
+        node: Node<'i, P::Rule, D, P::Error>,
+    ) -> Result<T, P::Error>;
+}
+
+#[doc(hidden)]
+pub struct ByMutRef;
+#[doc(hidden)]
+pub struct ByRef;
+
+impl<'i, P, D, T, F> ConsumeFn<'i, P, D, ByMutRef, T> for F
+where
+    P: Parser + ?Sized,
+    F: FnOnce(&mut P, Node<'i, P::Rule, D, P::Error>) -> Result<T, P::Error>,
+{
+    fn call(
+        self,
+        parser: &mut P,
+        node: Node<'i, P::Rule, D, P::Error>,
+    ) -> Result<T, P::Error> {
+        self(parser, node)
+    }
+}
+
+impl<'i, P, D, T, F> ConsumeFn<'i, P, D, ByRef, T> for F
+where
+    P: Parser + ?Sized,
+    F: FnOnce(&P, Node<'i, P::Rule, D, P::Error>) -> Result<T, P::Error>,
+{
+    fn call(
+        self,
+        parser: &mut P,
+        node: Node<'i, P::Rule, D, P::Error>,
+    ) -> Result<T, P::Error> {
+        self(parser, node)
     }
 }
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use pest_meta::ast::{Expr, Rule as AstRule, RuleType};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse::{Parse, ParseStream, Result};
+use syn::{Error, Ident, LitStr, Token};
+
+use crate::grammar::Grammar;
+
+mod kw {
+    syn::custom_keyword!(grammar);
+}
+
+struct MacroInput {
+    grammar: LitStr,
+}
+
+impl Parse for MacroInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let _: kw::grammar = input.parse()?;
+        let _: Token![=] = input.parse()?;
+        let grammar = input.parse()?;
+        let _: Option<Token![,]> = input.parse()?;
+        Ok(MacroInput { grammar })
+    }
+}
+
+/// How many children with a given rule a node can have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Multiplicity {
+    One,
+    Optional,
+    Many,
+}
+
+/// The rules of the children of a node, in the order they first appear in the grammar.
+type Children = Vec<(String, Multiplicity)>;
+
+/// Children of a sequence: a rule that appears on both sides can appear several times.
+fn seq(mut left: Children, right: Children) -> Children {
+    for (rule, mult) in right {
+        match left.iter_mut().find(|(r, _)| *r == rule) {
+            Some((_, m)) => *m = Multiplicity::Many,
+            None => left.push((rule, mult)),
+        }
+    }
+    left
+}
+
+/// Children of a choice: a rule that only appears on one side may be missing.
+fn choice(left: Children, right: Children) -> Children {
+    let mut children = left.clone();
+    for (rule, m) in &mut children {
+        if !right.iter().any(|(r, _)| r == rule) {
+            *m = optional(*m);
+        }
+    }
+    for (rule, mult) in right {
+        match children.iter_mut().find(|(r, _)| *r == rule) {
+            Some((_, m)) => {
+                *m = match (*m, mult) {
+                    (Multiplicity::One, Multiplicity::One) => Multiplicity::One,
+                    (Multiplicity::Many, _) | (_, Multiplicity::Many) => {
+                        Multiplicity::Many
+                    }
+                    _ => Multiplicity::Optional,
+                }
+            }
+            None => children.push((rule, optional(mult))),
+        }
+    }
+    children
+}
+
+fn optional(mult: Multiplicity) -> Multiplicity {
+    match mult {
+        Multiplicity::One => Multiplicity::Optional,
+        mult => mult,
+    }
+}
+
+fn repeated(children: Children) -> Children {
+    children
+        .into_iter()
+        .map(|(rule, _)| (rule, Multiplicity::Many))
+        .collect()
+}
+
+struct ChildrenFinder<'a> {
+    rules: HashMap<&'a str, &'a AstRule>,
+    /// The silent rules being inlined, and whether they turned out to be recursive.
+    stack: Vec<(&'a str, bool)>,
+    /// Where to report unsupported expressions.
+    grammar_lit: &'a LitStr,
+}
+
+impl<'a> ChildrenFinder<'a> {
+    fn children(&mut self, expr: &'a Expr) -> Result<Children> {
+        Ok(match expr {
+            Expr::Ident(name) => match self.rules.get(name.as_str()) {
+                Some(rule) if rule.ty == RuleType::Silent => self.inline(rule)?,
+                Some(_) => vec![(name.clone(), Multiplicity::One)],
+                // A builtin rule, which doesn't produce nodes.
+                None => Vec::new(),
+            },
+            Expr::Seq(left, right) => {
+                let left = self.children(left)?;
+                seq(left, self.children(right)?)
+            }
+            Expr::Choice(left, right) => {
+                let left = self.children(left)?;
+                choice(left, self.children(right)?)
+            }
+            Expr::Opt(expr) => self
+                .children(expr)?
+                .into_iter()
+                .map(|(rule, mult)| (rule, optional(mult)))
+                .collect(),
+            Expr::Rep(expr)
+            | Expr::RepOnce(expr)
+            | Expr::RepExact(expr, _)
+            | Expr::RepMin(expr, _)
+            | Expr::RepMax(expr, _)
+            | Expr::RepMinMax(expr, _, _) => repeated(self.children(expr)?),
+            Expr::Push(expr) => self.children(expr)?,
+            // A tag names a node without changing the tree.
+            #[cfg(feature = "grammar-extras")]
+            Expr::NodeTag(expr, _) => self.children(expr)?,
+            // Literals and predicates don't produce nodes.
+            Expr::Str(_)
+            | Expr::Insens(_)
+            | Expr::Range(_, _)
+            | Expr::PeekSlice(_, _)
+            | Expr::Skip(_)
+            | Expr::PosPred(_)
+            | Expr::NegPred(_) => Vec::new(),
+            // Node tags, when another crate enables the `grammar-extras` feature of `pest_meta`
+            // but we don't.
+            #[allow(unreachable_patterns)]
+            expr => {
+                return Err(Error::new(
+                    self.grammar_lit.span(),
+                    format!(
+                        "unsupported expression `{}`; node tags need the `grammar-extras` \
+                         feature of pest_consume",
+                        expr
+                    ),
+                ))
+            }
+        })
+    }
+
+    /// Children of a silent rule, which appear directly in the rules that use it.
+    fn inline(&mut self, rule: &'a AstRule) -> Result<Children> {
+        if let Some(entry) =
+            self.stack.iter_mut().find(|(name, _)| *name == rule.name)
+        {
+            entry.1 = true;
+            return Ok(Vec::new());
+        }
+        self.stack.push((&rule.name, false));
+        let children = self.children(&rule.expr)?;
+        let (_, recursive) = self.stack.pop().unwrap();
+        Ok(if recursive {
+            repeated(children)
+        } else {
+            children
+        })
+    }
+}
+
+/// `record_list` -> `RecordList`, `HEX_DIGIT` -> `HexDigit`
+fn type_name(rule: &str) -> Ident {
+    let name: String = rule
+        .split('_')
+        .map(|part| {
+            let part = if part.chars().all(|c| !c.is_lowercase()) {
+                part.to_lowercase()
+            } else {
+                part.to_owned()
+            };
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    Ident::new(&name, Span::call_site())
+}
+
+pub fn cst(input: proc_macro::TokenStream) -> Result<TokenStream> {
+    let input: MacroInput = syn::parse(input)?;
+    let grammar = Grammar::load(&input.grammar)?;
+    let mut finder = ChildrenFinder {
+        rules: grammar
+            .rules
+            .iter()
+            .map(|rule| (rule.name.as_str(), rule))
+            .collect(),
+        stack: Vec::new(),
+        grammar_lit: &input.grammar,
+    };
+
+    let mut types = Vec::new();
+    for rule in &grammar.rules {
+        if rule.ty == RuleType::Silent {
+            continue;
+        }
+        let rule_name = Ident::new(&rule.name, Span::call_site());
+        let name = type_name(&rule.name);
+        let struct_doc = format!("A node of the `{}` rule.", rule.name);
+
+        // The children of an atomic rule are not kept in the parse tree.
+        let children = if rule.ty == RuleType::Atomic {
+            Vec::new()
+        } else {
+            finder.children(&rule.expr)?
+        };
+        let accessors = children.iter().map(|(child, mult)| {
+            let child_rule = Ident::new(child, Span::call_site());
+            let child_type = type_name(child);
+            let find = quote!(
+                self.node.children().filter(|node| node.as_rule() == Rule::#child_rule)
+            );
+            let (ty, body) = match mult {
+                // The grammar guarantees the child, unless the node comes from another grammar.
+                Multiplicity::One => (
+                    quote!(::std::result::Result<#child_type<'i, D, E>, E>),
+                    quote!(match #find.next() {
+                        ::std::option::Option::Some(node) => {
+                            ::std::result::Result::Ok(#child_type { node })
+                        }
+                        ::std::option::Option::None => {
+                            ::std::result::Result::Err(self.node.error(format!(
+                                "expected a `{}` child",
+                                self.node.rule_name(Rule::#child_rule),
+                            )))
+                        }
+                    }),
+                ),
+                Multiplicity::Optional => (
+                    quote!(::std::option::Option<#child_type<'i, D, E>>),
+                    quote!(#find.next().map(|node| #child_type { node })),
+                ),
+                Multiplicity::Many => (
+                    quote!(::std::vec::Vec<#child_type<'i, D, E>>),
+                    quote!(#find.map(|node| #child_type { node }).collect()),
+                ),
+            };
+            let doc = match mult {
+                Multiplicity::One => format!(
+                    "The `{}` child, which is only missing if the node was parsed with another \
+                     grammar.",
+                    child
+                ),
+                Multiplicity::Optional => {
+                    format!("The `{}` child, if any.", child)
+                }
+                Multiplicity::Many => format!("The `{}` children.", child),
+            };
+            let bound = match mult {
+                Multiplicity::One => {
+                    quote!(where E: ::pest_consume::ParseError<Rule>)
+                }
+                _ => quote!(),
+            };
+            quote!(
+                #[doc = #doc]
+                #[allow(non_snake_case)]
+                pub fn #child_rule(&self) -> #ty #bound {
+                    #body
+                }
+            )
+        });
+
+        types.push(quote!(
+            #[doc = #struct_doc]
+            pub struct #name<'i, D = (), E = ::pest_consume::Error<Rule>> {
+                node: ::pest_consume::Node<'i, Rule, D, E>,
+            }
+
+            impl<'i, D, E> #name<'i, D, E> {
+                /// The node this wraps.
+                pub fn node(&self) -> &::pest_consume::Node<'i, Rule, D, E> {
+                    &self.node
+                }
+                pub fn into_node(self) -> ::pest_consume::Node<'i, Rule, D, E> {
+                    self.node
+                }
+                pub fn as_str(&self) -> &'i str {
+                    self.node.as_str()
+                }
+                pub fn as_span(&self) -> ::pest_consume::pest::Span<'i> {
+                    self.node.as_span()
+                }
+                #(#accessors)*
+            }
+
+            impl<'i, D, E> ::std::convert::TryFrom<::pest_consume::Node<'i, Rule, D, E>>
+                for #name<'i, D, E>
+            where
+                E: ::pest_consume::ParseError<Rule>,
+            {
+                type Error = E;
+                fn try_from(
+                    node: ::pest_consume::Node<'i, Rule, D, E>,
+                ) -> ::std::result::Result<Self, E> {
+                    if node.as_rule() == Rule::#rule_name {
+                        ::std::result::Result::Ok(#name { node })
+                    } else {
+                        ::std::result::Result::Err(node.error(format!(
+                            "expected `{}`, found `{}`",
+                            node.rule_name(Rule::#rule_name),
+                            node.rule_name(node.as_rule()),
+                        )))
+                    }
+                }
+            }
+
+            impl<'i, D, E> ::pest_consume::FromNode<'i, Rule, D, E> for #name<'i, D, E>
+            where
+                E: ::pest_consume::ParseError<Rule>,
+            {
+                fn rules() -> ::std::vec::Vec<Rule> {
+                    vec![Rule::#rule_name]
+                }
+                fn matches_rule(rule: Rule) -> bool {
+                    rule == Rule::#rule_name
+                }
+                fn from_node(
+                    node: ::pest_consume::Node<'i, Rule, D, E>,
+                ) -> ::std::result::Result<Self, E> {
+                    ::std::convert::TryFrom::try_from(node)
+                }
+            }
+
+            impl<'i, D, E> ::std::clone::Clone for #name<'i, D, E> {
+                fn clone(&self) -> Self {
+                    #name { node: self.node.clone() }
+                }
+            }
+
+            impl<'i, D: ::std::fmt::Debug, E> ::std::fmt::Debug for #name<'i, D, E> {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    f.debug_tuple(stringify!(#name)).field(&self.node).finish()
+                }
+            }
+        ));
+    }
+
+    // Make cargo rebuild when the grammar changes.
+    let path = grammar.path.to_string_lossy();
+    Ok(quote!(
+        const _: &str = include_str!(#path);
+        #(#types)*
+    ))
+}
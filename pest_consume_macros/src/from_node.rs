@@ -0,0 +1,505 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse::Result;
+use syn::spanned::Spanned;
+use syn::{
+    parse_quote, Attribute, Data, DeriveInput, Error, Field, Fields,
+    GenericArgument, Ident, Lifetime, Path, PathArguments, Type,
+};
+
+/// How the derived impl refers to the types it deals with.
+struct Context {
+    rule_enum: Path,
+    /// `::pest_consume::FromNode<'i, Rule, ___D, ___E>`
+    from_node: TokenStream,
+}
+
+/// A value taken from the text of a node.
+enum Leaf {
+    Str,
+    Span,
+    FromStr,
+}
+
+/// How many children a field stands for.
+enum Multiplicity {
+    Single,
+    Optional,
+    Multiple,
+}
+
+/// The children a field stands for.
+enum ChildItem {
+    /// Children with the given rule, whose text is converted.
+    Rule(Path, Leaf),
+    /// Children built with `FromNode`.
+    Node(Box<Type>),
+}
+
+enum FieldKind {
+    /// Taken from the node itself.
+    Own(Leaf),
+    Children(Multiplicity, ChildItem),
+}
+
+/// Reads a `#[rule(...)]` attribute, if any.
+fn rule_attr(attrs: &[Attribute]) -> Result<Option<Path>> {
+    let mut rules = attrs.iter().filter(|attr| attr.path.is_ident("rule"));
+    let rule = match rules.next() {
+        Some(attr) => attr.parse_args::<Path>()?,
+        None => return Ok(None),
+    };
+    if let Some(attr) = rules.next() {
+        return Err(Error::new(
+            attr.span(),
+            "expected at most one `rule` attribute",
+        ));
+    }
+    Ok(Some(rule))
+}
+
+fn has_attr(attrs: &[Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| attr.path.is_ident(name))
+}
+
+/// Resolves a rule name given without a path to a variant of `rule_enum`.
+fn rule_path(rule: Path, rule_enum: &Path) -> Path {
+    if rule.segments.len() == 1 {
+        parse_quote!(#rule_enum::#rule)
+    } else {
+        rule
+    }
+}
+
+/// Splits `T` out of `Option<T>` or `Vec<T>`.
+fn unwrap_container(ty: &Type) -> (Multiplicity, &Type) {
+    if let Type::Path(ty_path) = ty {
+        let last = ty_path.path.segments.last().unwrap();
+        let multiplicity = if last.ident == "Option" {
+            Multiplicity::Optional
+        } else if last.ident == "Vec" {
+            Multiplicity::Multiple
+        } else {
+            return (Multiplicity::Single, ty);
+        };
+        if let PathArguments::AngleBracketed(args) = &last.arguments {
+            if let (1, Some(GenericArgument::Type(inner))) =
+                (args.args.len(), args.args.first())
+            {
+                return (multiplicity, inner);
+            }
+        }
+    }
+    (Multiplicity::Single, ty)
+}
+
+/// Recognizes `&str` and `Span`, which are taken from the text of a node as-is.
+fn leaf_type(ty: &Type) -> Option<Leaf> {
+    match ty {
+        Type::Reference(reference) => match &*reference.elem {
+            Type::Path(elem) if elem.path.is_ident("str") => Some(Leaf::Str),
+            _ => None,
+        },
+        Type::Path(ty_path) => match ty_path.path.segments.last() {
+            Some(last) if last.ident == "Span" => Some(Leaf::Span),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn field_kind(field: &Field, ctx: &Context) -> Result<FieldKind> {
+    let rule = rule_attr(&field.attrs)?;
+    let from_str = field.attrs.iter().find(|a| a.path.is_ident("from_str"));
+    let (multiplicity, inner) = unwrap_container(&field.ty);
+    Ok(match (rule, from_str) {
+        (Some(_), Some(attr)) => {
+            return Err(Error::new(
+                attr.span(),
+                "the children given by `rule` are already parsed with `FromStr`",
+            ))
+        }
+        (Some(rule), None) => {
+            let rule = rule_path(rule, &ctx.rule_enum);
+            let leaf = leaf_type(inner).unwrap_or(Leaf::FromStr);
+            FieldKind::Children(multiplicity, ChildItem::Rule(rule, leaf))
+        }
+        (None, Some(attr)) => {
+            if let Multiplicity::Single = multiplicity {
+                FieldKind::Own(Leaf::FromStr)
+            } else {
+                return Err(Error::new(
+                    attr.span(),
+                    "`from_str` parses the text of the node itself; use `rule` to parse children",
+                ));
+            }
+        }
+        (None, None) => match (leaf_type(&field.ty), leaf_type(inner)) {
+            (Some(leaf), _) => FieldKind::Own(leaf),
+            (None, Some(_)) => {
+                return Err(Error::new(
+                    field.ty.span(),
+                    "expected a `rule` attribute saying which children this field stands for",
+                ))
+            }
+            (None, None) => FieldKind::Children(
+                multiplicity,
+                ChildItem::Node(Box::new(inner.clone())),
+            ),
+        },
+    })
+}
+
+/// Converts the child node `___c`.
+fn convert_child(item: &ChildItem, ctx: &Context) -> TokenStream {
+    let from_node = &ctx.from_node;
+    match item {
+        ChildItem::Rule(_, Leaf::Str) => quote!(___c.as_str()),
+        ChildItem::Rule(_, Leaf::Span) => quote!(___c.as_span()),
+        ChildItem::Rule(_, Leaf::FromStr) => {
+            quote!(___c.as_str().parse().map_err(|e| ___c.error(e))?)
+        }
+        ChildItem::Node(ty) => quote!(<#ty as #from_node>::from_node(___c)?),
+    }
+}
+
+/// Builds an expression that fills in `fields` from `___node`, and evaluates to
+/// `Result<Self, ___E>`.
+fn build_fields(
+    fields: &Fields,
+    ctor: TokenStream,
+    ctx: &Context,
+) -> Result<TokenStream> {
+    let from_node = &ctx.from_node;
+    let kinds = fields
+        .iter()
+        .map(|field| field_kind(field, ctx))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut items = Vec::new();
+    let mut item_names = Vec::new();
+    for kind in &kinds {
+        let (multiplicity, item) = match kind {
+            FieldKind::Children(multiplicity, item) => (multiplicity, item),
+            FieldKind::Own(_) => continue,
+        };
+        let (multiplicity, suffix) = match multiplicity {
+            Multiplicity::Single => (quote!(Single), ""),
+            Multiplicity::Optional => (quote!(Optional), "?"),
+            Multiplicity::Multiple => (quote!(Multiple), ".."),
+        };
+        let (matches, names) = match item {
+            ChildItem::Rule(rule, _) => (
+                quote!(___children[___i].as_rule() == #rule),
+                quote!(___node.rule_name(#rule)),
+            ),
+            ChildItem::Node(ty) => (
+                quote!(<#ty as #from_node>::matches_rule(
+                    ___children[___i].as_rule()
+                )),
+                quote!(<#ty as #from_node>::rules()
+                    .into_iter()
+                    .map(|rule| ___node.rule_name(rule))
+                    .collect::<::std::vec::Vec<_>>()
+                    .join(" | ")),
+            ),
+        };
+        items.push(quote!((
+            ::pest_consume::Multiplicity::#multiplicity,
+            &|___i: usize| #matches,
+        )));
+        item_names.push(quote!(format!(concat!("{}", #suffix), #names)));
+    }
+
+    let match_children = if items.is_empty() {
+        quote!()
+    } else {
+        quote!(
+            let ___children: ::std::vec::Vec<_> = ___node.children().collect();
+            // A trailing `EOI` is not part of the value. `Rule::EOI` only exists if the
+            // grammar uses `EOI`, so we compare names, but only for empty nodes like `EOI`.
+            let ___len = match ___children.last() {
+                ::std::option::Option::Some(c)
+                    if c.as_str().is_empty()
+                        && format!("{:?}", c.as_rule()) == "EOI" =>
+                {
+                    ___children.len() - 1
+                }
+                _ => ___children.len(),
+            };
+            let ___counts = match ::pest_consume::match_pattern(&[#(#items),*], ___len) {
+                ::std::result::Result::Ok(counts) => counts,
+                ::std::result::Result::Err(___furthest) => {
+                    let expected: ::std::vec::Vec<::std::string::String> =
+                        vec![#(#item_names),*];
+                    let found: ::std::vec::Vec<_> = ___children
+                        .iter()
+                        .map(|c| ___node.rule_name(c.as_rule()))
+                        .collect();
+                    let message = format!(
+                        "expected `[{}]`, found `[{}]`",
+                        expected.join(", "),
+                        found.join(", "),
+                    );
+                    return ::std::result::Result::Err(
+                        match ___children.get(___furthest) {
+                            ::std::option::Option::Some(c) => c.error(message),
+                            ::std::option::Option::None => ___node.error(message),
+                        },
+                    );
+                }
+            };
+            let mut ___children = ___children.into_iter();
+        )
+    };
+
+    let mut values = Vec::new();
+    let mut item_index = 0usize;
+    for kind in &kinds {
+        let value = match kind {
+            FieldKind::Own(Leaf::Str) => quote!(___node.as_str()),
+            FieldKind::Own(Leaf::Span) => quote!(___node.as_span()),
+            FieldKind::Own(Leaf::FromStr) => {
+                quote!(___node.as_str().parse().map_err(|e| ___node.error(e))?)
+            }
+            FieldKind::Children(multiplicity, item) => {
+                let convert = convert_child(item, ctx);
+                let i = item_index;
+                item_index += 1;
+                match multiplicity {
+                    Multiplicity::Single => quote!({
+                        let ___c = ___children.next().unwrap();
+                        #convert
+                    }),
+                    Multiplicity::Optional => quote!(if ___counts[#i] == 1 {
+                        let ___c = ___children.next().unwrap();
+                        ::std::option::Option::Some(#convert)
+                    } else {
+                        ::std::option::Option::None
+                    }),
+                    Multiplicity::Multiple => quote!({
+                        let mut ___values =
+                            ::std::vec::Vec::with_capacity(___counts[#i]);
+                        for ___c in ___children.by_ref().take(___counts[#i]) {
+                            ___values.push(#convert);
+                        }
+                        ___values
+                    }),
+                }
+            }
+        };
+        values.push(value);
+    }
+
+    let i_values: Vec<_> = (0..values.len())
+        .map(|i| Ident::new(&format!("___field{}", i), Span::call_site()))
+        .collect();
+    let tys = fields.iter().map(|field| &field.ty);
+    let constructed = match fields {
+        Fields::Named(_) => {
+            let names = fields.iter().map(|field| &field.ident);
+            quote!(#ctor { #(#names: #i_values),* })
+        }
+        Fields::Unnamed(_) => quote!(#ctor(#(#i_values),*)),
+        Fields::Unit => quote!(#ctor),
+    };
+    Ok(quote!({
+        #match_children
+        #(let #i_values: #tys = #values;)*
+        ::std::result::Result::Ok(#constructed)
+    }))
+}
+
+/// An error saying that `___node` doesn't have one of the rules in `rules`.
+fn unexpected_rule(rules: TokenStream) -> TokenStream {
+    quote!({
+        let expected: ::std::vec::Vec<_> = #rules
+            .into_iter()
+            .map(|rule| format!("`{}`", ___node.rule_name(rule)))
+            .collect();
+        let expected = if expected.len() == 1 {
+            expected.join("")
+        } else {
+            format!("one of {}", expected.join(", "))
+        };
+        ___node.error(format!(
+            "expected {}, found `{}`",
+            expected,
+            ___node.rule_name(___node.as_rule()),
+        ))
+    })
+}
+
+pub fn derive_from_node(
+    input: proc_macro::TokenStream,
+) -> Result<proc_macro2::TokenStream> {
+    let input: DeriveInput = syn::parse(input)?;
+    let rule = rule_attr(&input.attrs)?;
+    let rule_enum: Path = match &rule {
+        Some(rule) if rule.segments.len() > 1 => Path {
+            leading_colon: rule.leading_colon,
+            segments: rule
+                .segments
+                .iter()
+                .take(rule.segments.len() - 1)
+                .cloned()
+                .collect(),
+        },
+        _ => parse_quote!(Rule),
+    };
+    let rule = rule.map(|rule| rule_path(rule, &rule_enum));
+
+    // The lifetime of the type, if any, is the lifetime of the input.
+    let mut generics = input.generics.clone();
+    let lifetime: Lifetime = match input.generics.lifetimes().next() {
+        Some(def) => def.lifetime.clone(),
+        None => {
+            let lifetime: Lifetime = parse_quote!('___i);
+            generics.params.insert(0, parse_quote!(#lifetime));
+            lifetime
+        }
+    };
+    generics.params.push(parse_quote!(___D));
+    generics
+        .params
+        .push(parse_quote!(___E: ::pest_consume::ParseError<#rule_enum>));
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    let ctx = Context {
+        from_node: quote!(::pest_consume::FromNode<#lifetime, #rule_enum, ___D, ___E>),
+        rule_enum,
+    };
+    let from_node = &ctx.from_node;
+    let rule_enum = &ctx.rule_enum;
+
+    // Checks the rule of a node of a type with a `rule` attribute.
+    let check_rule = |rule: &Path| {
+        let error = unexpected_rule(quote!(vec![#rule]));
+        quote!(
+            if ___node.as_rule() != #rule {
+                return ::std::result::Result::Err(#error);
+            }
+        )
+    };
+
+    let (rules, matches_rule, body) = match &input.data {
+        Data::Struct(data) => {
+            let rule = rule.ok_or_else(|| {
+                Error::new(
+                    input.ident.span(),
+                    "expected a `#[rule(...)]` attribute giving the rule of the nodes to build this from",
+                )
+            })?;
+            let check = check_rule(&rule);
+            let build = build_fields(&data.fields, quote!(Self), &ctx)?;
+            (
+                quote!(vec![#rule]),
+                quote!(rule == #rule),
+                quote!(#check #build),
+            )
+        }
+        Data::Enum(data) => {
+            let mut variant_rules = Vec::new();
+            let mut variant_matches = Vec::new();
+            let mut branches = Vec::new();
+            for variant in &data.variants {
+                let name = &variant.ident;
+                match (rule_attr(&variant.attrs)?, &variant.fields) {
+                    (Some(variant_rule), fields) => {
+                        let variant_rule = rule_path(variant_rule, rule_enum);
+                        let build =
+                            build_fields(fields, quote!(Self::#name), &ctx)?;
+                        variant_rules.push(quote!(rules.push(#variant_rule);));
+                        variant_matches.push(quote!(rule == #variant_rule));
+                        branches.push(quote!(
+                            if ___node.as_rule() == #variant_rule {
+                                return #build;
+                            }
+                        ));
+                    }
+                    (None, Fields::Unnamed(fields))
+                        if fields.unnamed.len() == 1
+                            && !has_attr(&fields.unnamed[0].attrs, "rule")
+                            && !has_attr(
+                                &fields.unnamed[0].attrs,
+                                "from_str",
+                            ) =>
+                    {
+                        let ty = &fields.unnamed[0].ty;
+                        variant_rules.push(quote!(
+                            rules.extend(<#ty as #from_node>::rules());
+                        ));
+                        variant_matches
+                            .push(quote!(<#ty as #from_node>::matches_rule(rule)));
+                        branches.push(quote!(
+                            if <#ty as #from_node>::matches_rule(___node.as_rule()) {
+                                return <#ty as #from_node>::from_node(___node)
+                                    .map(Self::#name);
+                            }
+                        ));
+                    }
+                    (None, _) => {
+                        return Err(Error::new(
+                            variant.span(),
+                            "expected a `#[rule(...)]` attribute, or a single field whose type implements `FromNode`",
+                        ))
+                    }
+                }
+            }
+            let variant_rules = quote!({
+                let mut rules = ::std::vec::Vec::new();
+                #(#variant_rules)*
+                rules
+            });
+            let error = unexpected_rule(variant_rules.clone());
+            let dispatch = quote!(
+                #(#branches)*
+                ::std::result::Result::Err(#error)
+            );
+            match rule {
+                // The variant is chosen from the only child of the node.
+                Some(rule) => {
+                    let check = check_rule(&rule);
+                    (
+                        quote!(vec![#rule]),
+                        quote!(rule == #rule),
+                        quote!(
+                            #check
+                            let ___node = ___node.into_children().single()?;
+                            #dispatch
+                        ),
+                    )
+                }
+                None => (
+                    variant_rules,
+                    quote!(false #(|| #variant_matches)*),
+                    dispatch,
+                ),
+            }
+        }
+        Data::Union(_) => {
+            return Err(Error::new(
+                input.ident.span(),
+                "`FromNode` can't be derived for unions",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    Ok(quote!(
+        impl #impl_generics #from_node for #name #ty_generics #where_clause {
+            fn rules() -> ::std::vec::Vec<#rule_enum> {
+                #rules
+            }
+            fn matches_rule(rule: #rule_enum) -> bool {
+                #matches_rule
+            }
+            fn from_node(
+                ___node: ::pest_consume::Node<#lifetime, #rule_enum, ___D, ___E>,
+            ) -> ::std::result::Result<Self, ___E> {
+                #body
+            }
+        }
+    ))
+}
@@ -0,0 +1,63 @@
+use std::path::{Path, PathBuf};
+
+use pest_meta::ast::{Rule as AstRule, RuleType};
+use pest_meta::parser::{self, Rule as MetaRule};
+use syn::parse::Result;
+use syn::{Error, LitStr};
+
+/// A pest grammar file, as read by the macros that need to inspect it.
+pub struct Grammar {
+    /// Absolute path of the grammar file.
+    pub path: PathBuf,
+    pub rules: Vec<AstRule>,
+}
+
+impl Grammar {
+    /// Loads a grammar file, resolving the path the same way `pest_derive` does: relative to the
+    /// crate root if such a file exists, and otherwise relative to the `src/` directory.
+    pub fn load(path_lit: &LitStr) -> Result<Self> {
+        let root =
+            std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".into());
+        let root = Path::new(&root);
+        let rel_path = path_lit.value();
+        let path = if root.join(&rel_path).exists() {
+            root.join(&rel_path)
+        } else {
+            root.join("src/").join(&rel_path)
+        };
+
+        let data = std::fs::read_to_string(&path).map_err(|e| {
+            Error::new(
+                path_lit.span(),
+                format!("error opening {}: {}", path.display(), e),
+            )
+        })?;
+        let rules = parser::parse(MetaRule::grammar_rules, &data)
+            .map_err(|e| vec![e])
+            .and_then(parser::consume_rules)
+            .map_err(|errors| {
+                let errors: Vec<_> =
+                    errors.iter().map(|e| e.to_string()).collect();
+                Error::new(
+                    path_lit.span(),
+                    format!(
+                        "error parsing {}:\n{}",
+                        path.display(),
+                        errors.join("\n")
+                    ),
+                )
+            })?;
+
+        Ok(Grammar { path, rules })
+    }
+
+    /// Names of the rules that can appear in a parse tree, i.e. the non-silent rules and `EOI`.
+    pub fn non_silent_rules(&self) -> Vec<&str> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.ty != RuleType::Silent)
+            .map(|rule| rule.name.as_str())
+            .chain(std::iter::once("EOI"))
+            .collect()
+    }
+}
@@ -5,6 +5,9 @@
 
 extern crate proc_macro;
 
+mod cst;
+mod from_node;
+mod grammar;
 mod make_parser;
 mod match_nodes;
 
@@ -27,3 +30,21 @@ pub fn match_nodes(input: TokenStream) -> TokenStream {
         Err(err) => err.to_compile_error(),
     })
 }
+
+/// See [pest_consume](https://docs.rs/pest_consume) for documentation.
+#[proc_macro]
+pub fn cst(input: TokenStream) -> TokenStream {
+    TokenStream::from(match cst::cst(input) {
+        Ok(tokens) => tokens,
+        Err(err) => err.to_compile_error(),
+    })
+}
+
+/// See [pest_consume](https://docs.rs/pest_consume) for documentation.
+#[proc_macro_derive(FromNode, attributes(rule, from_str))]
+pub fn from_node(input: TokenStream) -> TokenStream {
+    TokenStream::from(match from_node::derive_from_node(input) {
+        Ok(tokens) => tokens,
+        Err(err) => err.to_compile_error(),
+    })
+}
@@ -1,14 +1,19 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::iter;
 
+use proc_macro2::{Span, TokenStream, TokenTree};
 use quote::quote;
 use syn::parse::{Parse, ParseStream, Result};
+use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::{
-    parse_quote, Error, Expr, FnArg, Ident, ImplItem, ImplItemMethod, ItemImpl,
-    LitBool, Pat, Path, Token,
+    parenthesized, parse_quote, token, Error, Expr, FnArg, GenericParam, Ident,
+    ImplItem, ImplItemMethod, Item, ItemImpl, Lit, LitBool, LitStr, Meta, Pat,
+    Path, Stmt, Token, Type, WherePredicate,
 };
 
+use crate::grammar::Grammar;
+
 /// Ext. trait adding `partition_filter` to `Vec`. Would like to use `Vec::drain_filter`
 /// but it's unstable for now.
 pub trait VecPartitionFilterExt<Item> {
@@ -39,11 +44,23 @@ mod kw {
     syn::custom_keyword!(shortcut);
     syn::custom_keyword!(rule);
     syn::custom_keyword!(parser);
+    syn::custom_keyword!(grammar);
+    syn::custom_keyword!(error);
+    syn::custom_keyword!(exhaustive);
+    syn::custom_keyword!(stateful);
+    syn::custom_keyword!(prefix);
+    syn::custom_keyword!(postfix);
+    syn::custom_keyword!(left);
+    syn::custom_keyword!(right);
 }
 
 struct MakeParserAttrs {
     parser: Path,
     rule_enum: Path,
+    error: Option<Type>,
+    grammar: Option<LitStr>,
+    exhaustive: Option<kw::exhaustive>,
+    stateful: bool,
 }
 
 struct AliasArgs {
@@ -53,7 +70,41 @@ struct AliasArgs {
 
 struct PrecClimbArgs {
     child_rule: Ident,
-    climber: Expr,
+    climber: PrecClimber,
+}
+
+enum PrecClimber {
+    /// An expression that references a `PrecClimber` or a `PrattParser`.
+    Expr(Box<Expr>),
+    /// An inline operator table, from lowest to highest precedence.
+    Table(Vec<OperatorLevel>),
+}
+
+/// A set of operators with the same precedence, e.g. `left(plus, minus)`.
+struct OperatorLevel {
+    fixity: Fixity,
+    rules: Punctuated<Ident, Token![,]>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Fixity {
+    Left,
+    Right,
+    Prefix,
+    Postfix,
+}
+
+struct PrattArgs {
+    child_rule: Ident,
+    pratt: PrecClimber,
+}
+
+/// The methods marked with `pratt_prefix` or `pratt_postfix`, by the `pratt` method they handle
+/// operators for.
+#[derive(Default)]
+struct PrattHelpers {
+    prefix: HashMap<Ident, Ident>,
+    postfix: HashMap<Ident, Ident>,
 }
 
 struct AliasSrc {
@@ -68,6 +119,8 @@ struct ParsedFn<'a> {
     fn_name: Ident,
     // Name of the first argument of the function, which should be of type `Node`.
     input_arg: Ident,
+    // Names of the other arguments, passed along when calling another method.
+    extra_args: Vec<Ident>,
     // List of aliases pointing to this function
     alias_srcs: Vec<AliasSrc>,
 }
@@ -78,6 +131,11 @@ impl Parse for MakeParserAttrs {
         let mut parser = parse_quote!(Self);
         // By default, use the `Rule` type in scope
         let mut rule_enum = parse_quote!(Rule);
+        // By default, use pest's error type
+        let mut error = None;
+        let mut grammar = None;
+        let mut exhaustive: Option<kw::exhaustive> = None;
+        let mut stateful = false;
 
         while !input.is_empty() {
             let lookahead = input.lookahead1();
@@ -89,6 +147,19 @@ impl Parse for MakeParserAttrs {
                 let _: kw::rule = input.parse()?;
                 let _: Token![=] = input.parse()?;
                 rule_enum = input.parse()?;
+            } else if lookahead.peek(kw::error) {
+                let _: kw::error = input.parse()?;
+                let _: Token![=] = input.parse()?;
+                error = Some(input.parse()?);
+            } else if lookahead.peek(kw::grammar) {
+                let _: kw::grammar = input.parse()?;
+                let _: Token![=] = input.parse()?;
+                grammar = Some(input.parse()?);
+            } else if lookahead.peek(kw::exhaustive) {
+                exhaustive = Some(input.parse()?);
+            } else if lookahead.peek(kw::stateful) {
+                let _: kw::stateful = input.parse()?;
+                stateful = true;
             } else {
                 return Err(lookahead.error());
             }
@@ -100,7 +171,21 @@ impl Parse for MakeParserAttrs {
             }
         }
 
-        Ok(MakeParserAttrs { parser, rule_enum })
+        if let (Some(exhaustive), None) = (&exhaustive, &grammar) {
+            return Err(Error::new(
+                exhaustive.span(),
+                "`exhaustive` requires the grammar file to be provided with `grammar = \"...\"`",
+            ));
+        }
+
+        Ok(MakeParserAttrs {
+            parser,
+            rule_enum,
+            error,
+            grammar,
+            exhaustive,
+            stateful,
+        })
     }
 }
 
@@ -130,6 +215,16 @@ impl Parse for PrecClimbArgs {
         let child_rule = input.parse()?;
         let _: Token![,] = input.parse()?;
         let climber = input.parse()?;
+        if let PrecClimber::Table(levels) = &climber {
+            for level in levels {
+                if let Fixity::Prefix | Fixity::Postfix = level.fixity {
+                    return Err(Error::new(
+                        level.rules.span(),
+                        "prec_climb only supports infix operators, use pratt instead",
+                    ));
+                }
+            }
+        }
         Ok(PrecClimbArgs {
             child_rule,
             climber,
@@ -137,13 +232,141 @@ impl Parse for PrecClimbArgs {
     }
 }
 
+impl OperatorLevel {
+    fn peek(input: ParseStream) -> bool {
+        (input.peek(kw::left)
+            || input.peek(kw::right)
+            || input.peek(kw::prefix)
+            || input.peek(kw::postfix))
+            && input.peek2(token::Paren)
+    }
+}
+
+impl Parse for OperatorLevel {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let lookahead = input.lookahead1();
+        let fixity = if lookahead.peek(kw::left) {
+            let _: kw::left = input.parse()?;
+            Fixity::Left
+        } else if lookahead.peek(kw::right) {
+            let _: kw::right = input.parse()?;
+            Fixity::Right
+        } else if lookahead.peek(kw::prefix) {
+            let _: kw::prefix = input.parse()?;
+            Fixity::Prefix
+        } else if lookahead.peek(kw::postfix) {
+            let _: kw::postfix = input.parse()?;
+            Fixity::Postfix
+        } else {
+            return Err(lookahead.error());
+        };
+        let contents;
+        parenthesized!(contents in input);
+        let rules = Punctuated::parse_terminated(&contents)?;
+        if rules.is_empty() {
+            return Err(contents.error("expected at least one operator rule"));
+        }
+        Ok(OperatorLevel { fixity, rules })
+    }
+}
+
+impl Parse for PrecClimber {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if OperatorLevel::peek(input) {
+            // #[pratt(term, left(plus, minus), right(pow), prefix(neg))]
+            let levels: Punctuated<OperatorLevel, Token![,]> =
+                Punctuated::parse_terminated(input)?;
+            Ok(PrecClimber::Table(levels.into_iter().collect()))
+        } else {
+            // #[pratt(term, PRATT)]
+            Ok(PrecClimber::Expr(input.parse()?))
+        }
+    }
+}
+
+impl Parse for PrattArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let child_rule = input.parse()?;
+        let _: Token![,] = input.parse()?;
+        let pratt = input.parse()?;
+        Ok(PrattArgs { child_rule, pratt })
+    }
+}
+
+/// Collects the methods marked with `pratt_prefix(method)` or `pratt_postfix(method)`, and removes
+/// those attributes. Those methods are plain helper functions and not rule methods.
+fn collect_pratt_helpers(
+    imp: &mut ItemImpl,
+) -> Result<(Vec<Ident>, PrattHelpers)> {
+    let mut names = Vec::new();
+    let mut helpers = PrattHelpers::default();
+    for item in &mut imp.items {
+        if let ImplItem::Method(m) = item {
+            let attrs = m.attrs.partition_filter(|attr| {
+                attr.path.is_ident("pratt_prefix")
+                    || attr.path.is_ident("pratt_postfix")
+            });
+            for attr in attrs {
+                let target: Ident = attr.parse_args()?;
+                let map = if attr.path.is_ident("pratt_prefix") {
+                    &mut helpers.prefix
+                } else {
+                    &mut helpers.postfix
+                };
+                if map.insert(target, m.sig.ident.clone()).is_some() {
+                    return Err(Error::new(
+                        attr.span(),
+                        "a pratt method can only have one handler of each kind",
+                    ));
+                }
+                names.push(m.sig.ident.clone());
+            }
+        }
+    }
+
+    // Each handler must refer to a `pratt` method.
+    let pratt_methods: Vec<&Ident> = imp
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            ImplItem::Method(m)
+                if m.attrs.iter().any(|attr| attr.path.is_ident("pratt")) =>
+            {
+                Some(&m.sig.ident)
+            }
+            _ => None,
+        })
+        .collect();
+    for (target, helper) in helpers.prefix.iter().chain(&helpers.postfix) {
+        if !pratt_methods.contains(&target) {
+            return Err(Error::new(
+                target.span(),
+                format!(
+                    "`{}` handles the operators of `{}`, which doesn't have a pratt attribute",
+                    helper, target
+                ),
+            ));
+        }
+    }
+    Ok((names, helpers))
+}
+
+/// Iterates over the methods of the impl block that correspond to a rule.
+fn rule_methods<'a>(
+    imp: &'a mut ItemImpl,
+    helpers: &'a [Ident],
+) -> impl Iterator<Item = &'a mut ImplItemMethod> {
+    imp.items.iter_mut().flat_map(move |item| match item {
+        ImplItem::Method(m) if !helpers.contains(&m.sig.ident) => Some(m),
+        _ => None,
+    })
+}
+
 fn collect_aliases(
     imp: &mut ItemImpl,
+    helpers: &[Ident],
 ) -> Result<HashMap<Ident, Vec<AliasSrc>>> {
-    let functions = imp.items.iter_mut().flat_map(|item| match item {
-        ImplItem::Method(m) => Some(m),
-        _ => None,
-    });
+    let functions = rule_methods(imp, helpers);
 
     let mut alias_map = HashMap::new();
     for function in functions {
@@ -182,22 +405,117 @@ fn collect_aliases(
     Ok(alias_map)
 }
 
-fn extract_ident_argument(input_arg: &FnArg) -> Result<Ident> {
-    match input_arg {
-        FnArg::Receiver(_) => {
+/// Collects the `#[display_name = "..."]` attributes of the rule methods, keyed by method name.
+fn collect_display_names(
+    imp: &mut ItemImpl,
+    helpers: &[Ident],
+) -> Result<Vec<(Ident, LitStr)>> {
+    let mut display_names = Vec::new();
+    for function in rule_methods(imp, helpers) {
+        let mut attrs = function
+            .attrs
+            .partition_filter(|attr| attr.path.is_ident("display_name"))
+            .into_iter();
+        if let Some(attr) = attrs.next() {
+            match attr.parse_meta()? {
+                Meta::NameValue(meta) => match meta.lit {
+                    Lit::Str(name) => {
+                        display_names.push((function.sig.ident.clone(), name))
+                    }
+                    lit => {
+                        return Err(Error::new(
+                            lit.span(),
+                            "expected a string literal",
+                        ))
+                    }
+                },
+                meta => {
+                    return Err(Error::new(
+                        meta.span(),
+                        "expected `#[display_name = \"...\"]`",
+                    ))
+                }
+            }
+        }
+        if let Some(attr) = attrs.next() {
             return Err(Error::new(
-                input_arg.span(),
-                "this argument should not be `self`",
-            ))
+                attr.span(),
+                "expected at most one display_name attribute",
+            ));
+        }
+    }
+    Ok(display_names)
+}
+
+/// Checks that the methods of the impl block are in one-to-one correspondence with the rules of
+/// the grammar that can appear in a parse tree.
+fn check_exhaustive(
+    imp: &ItemImpl,
+    helpers: &[Ident],
+    grammar: &Grammar,
+    grammar_lit: &LitStr,
+) -> Result<()> {
+    let rules = grammar.non_silent_rules();
+    let methods: Vec<&Ident> = imp
+        .items
+        .iter()
+        .flat_map(|item| match item {
+            ImplItem::Method(m) => Some(&m.sig.ident),
+            _ => None,
+        })
+        .filter(|ident| !helpers.contains(ident))
+        .collect();
+
+    let mut errors = Vec::new();
+    let missing_methods: Vec<&str> = rules
+        .iter()
+        .copied()
+        // An `EOI` method is generated if there is none.
+        .filter(|rule| *rule != "EOI" && !methods.iter().any(|m| m == rule))
+        .collect();
+    if !missing_methods.is_empty() {
+        errors.push(Error::new(
+            grammar_lit.span(),
+            format!(
+                "the following rules do not have a corresponding method: {}",
+                missing_methods.join(", ")
+            ),
+        ));
+    }
+    for method in methods {
+        if !rules.iter().any(|rule| method == rule) {
+            errors.push(Error::new(
+                method.span(),
+                format!(
+                    "method `{}` does not correspond to a rule of the grammar",
+                    method
+                ),
+            ));
         }
+    }
+
+    match errors.into_iter().reduce(|mut acc, err| {
+        acc.combine(err);
+        acc
+    }) {
+        None => Ok(()),
+        Some(err) => Err(err),
+    }
+}
+
+fn extract_ident_argument(input_arg: &FnArg) -> Result<Ident> {
+    match input_arg {
+        FnArg::Receiver(_) => Err(Error::new(
+            input_arg.span(),
+            "this argument should not be `self`; methods can only take `self` in a parser \
+             declared with `#[pest_consume::parser(stateful)]`",
+        )),
         FnArg::Typed(input_arg) => match &*input_arg.pat {
             Pat::Ident(pat) => Ok(pat.ident.clone()),
-            _ => {
-                return Err(Error::new(
-                    input_arg.span(),
-                    "this argument should be a plain identifier instead of a pattern",
-                ))
-            }
+            _ => Err(Error::new(
+                input_arg.span(),
+                "this argument should be a plain identifier instead of a pattern",
+            )),
         },
     }
 }
@@ -205,28 +523,56 @@ fn extract_ident_argument(input_arg: &FnArg) -> Result<Ident> {
 fn parse_fn<'a>(
     function: &'a mut ImplItemMethod,
     alias_map: &mut HashMap<Ident, Vec<AliasSrc>>,
+    stateful: bool,
 ) -> Result<ParsedFn<'a>> {
-    if function.sig.inputs.len() != 1 {
+    // In a stateful parser, the `Node` comes after `&self` or `&mut self`.
+    let skip = if stateful {
+        match function.sig.inputs.first() {
+            Some(FnArg::Receiver(receiver)) if receiver.reference.is_some() => {
+                1
+            }
+            _ => {
+                return Err(Error::new(
+                    function.sig.span(),
+                    "in a stateful parser, rule methods must take `&self` or `&mut self`",
+                ))
+            }
+        }
+    } else {
+        0
+    };
+    if function.sig.inputs.len() <= skip {
         return Err(Error::new(
-            function.sig.inputs.span(),
-            "A rule method must have 1 argument",
+            function.sig.span(),
+            "A rule method must have at least 1 argument",
         ));
     }
 
     let fn_name = function.sig.ident.clone();
     // Get the name of the first function argument
-    let input_arg = extract_ident_argument(&function.sig.inputs[0])?;
-    let alias_srcs = alias_map.remove(&fn_name).unwrap_or_else(Vec::new);
+    let input_arg = extract_ident_argument(&function.sig.inputs[skip])?;
+    let extra_args = function
+        .sig
+        .inputs
+        .iter()
+        .skip(skip + 1)
+        .map(extract_ident_argument)
+        .collect::<Result<_>>()?;
+    let alias_srcs = alias_map.remove(&fn_name).unwrap_or_default();
 
     Ok(ParsedFn {
         function,
         fn_name,
         input_arg,
+        extra_args,
         alias_srcs,
     })
 }
 
-fn apply_prec_climb_attr(function: &mut ImplItemMethod) -> Result<()> {
+fn apply_prec_climb_attr(
+    function: &mut ImplItemMethod,
+    rule_enum: &Path,
+) -> Result<()> {
     // `prec_climb` attrs
     let mut prec_climb_attrs: Vec<_> = function
         .attrs
@@ -248,42 +594,502 @@ fn apply_prec_climb_attr(function: &mut ImplItemMethod) -> Result<()> {
         climber,
     } = args;
 
-    if function.sig.inputs.len() != 3 {
+    if function.sig.inputs.len() < 3 {
         return Err(Error::new(
             function.sig.inputs.span(),
-            "A prec_climb method must have 3 arguments",
+            "A prec_climb method must have at least 3 arguments",
         ));
     }
 
-    // Create a new function that only has the middle argument of the original one.
-    // It should have type Node and that way all the generic bits should work fine.
+    // Create a new function that only has the middle argument of the original one, followed by
+    // its extra arguments. It should have type Node and that way all the generic bits should work
+    // fine.
+    let (new_sig, arg_name, extra_args) = operator_method_sig(function)?;
+
+    let fn_name = &function.sig.ident;
+    let forward_args = forward_operator_args(&extra_args);
+    let climb = quote!(
+        let ___args = ::std::cell::RefCell::new((#(#extra_args,)*));
+        #arg_name
+            .into_children()
+            .prec_climb(
+                climber,
+                |node| {
+                    let ___args = &mut *___args.borrow_mut();
+                    Self::#child_rule(node #forward_args)
+                },
+                |left, op, right| {
+                    let ___args = &mut *___args.borrow_mut();
+                    #fn_name(left, op, right #forward_args)
+                },
+            )
+    );
+    let climb = match climber {
+        PrecClimber::Expr(climber) => quote!(
+            #[allow(deprecated)]
+            let climber = &*#climber;
+            #climb
+        ),
+        PrecClimber::Table(levels) => {
+            let pcl = quote!(::pest_consume::pest::prec_climber);
+            let levels = levels.iter().map(|level| {
+                let assoc = if level.fixity == Fixity::Left {
+                    quote!(#pcl::Assoc::Left)
+                } else {
+                    quote!(#pcl::Assoc::Right)
+                };
+                let ops = level.rules.iter().map(|rule| {
+                    quote!(#pcl::Operator::new(#rule_enum::#rule, #assoc))
+                });
+                quote!(#(#ops)|*)
+            });
+            // Build the climber only once per thread.
+            quote!(
+                #[allow(deprecated)]
+                fn ___with_climber<T>(
+                    f: impl FnOnce(&#pcl::PrecClimber<#rule_enum>) -> T,
+                ) -> T {
+                    thread_local! {
+                        static CLIMBER: #pcl::PrecClimber<#rule_enum> =
+                            #pcl::PrecClimber::new(::std::vec![#(#levels),*]);
+                    }
+                    CLIMBER.with(f)
+                }
+                ___with_climber(|climber| { #climb })
+            )
+        }
+    };
+
+    *function = parse_quote!(
+        #new_sig {
+            #function
+
+            #climb
+        }
+    );
+
+    Ok(())
+}
+
+/// Turns the signature of a `prec_climb` or `pratt` method, `(left, op, right, extra args...)`,
+/// into the signature of the method that consumes the whole expression, `(input, extra args...)`.
+/// Also returns the names of the input and of the extra arguments.
+fn operator_method_sig(
+    function: &ImplItemMethod,
+) -> Result<(syn::Signature, Ident, Vec<Ident>)> {
     let mut new_sig = function.sig.clone();
-    let arg = &new_sig.inputs[1];
-    let arg_name = extract_ident_argument(arg)?;
-    new_sig.inputs = std::iter::once(arg.clone()).collect();
+    let arg = new_sig.inputs[1].clone();
+    let arg_name = extract_ident_argument(&arg)?;
+    let extra_args: Vec<FnArg> =
+        new_sig.inputs.iter().skip(3).cloned().collect();
+    let extra_names = extra_args
+        .iter()
+        .map(extract_ident_argument)
+        .collect::<Result<_>>()?;
+    new_sig.inputs = iter::once(arg).chain(extra_args).collect();
+    Ok((new_sig, arg_name, extra_names))
+}
+
+/// The extra arguments of an operator method, as passed from the `___args` tuple that the
+/// callbacks share. They take turns borrowing it, since they are never called at the same time.
+fn forward_operator_args(extra_args: &[Ident]) -> TokenStream {
+    let indices = (0..extra_args.len()).map(syn::Index::from);
+    quote!(#(, ___args.#indices)*)
+}
+
+fn apply_pratt_attr(
+    function: &mut ImplItemMethod,
+    rule_enum: &Path,
+    helpers: &PrattHelpers,
+) -> Result<()> {
+    let mut pratt_attrs: Vec<_> = function
+        .attrs
+        .partition_filter(|attr| attr.path.is_ident("pratt"));
+
+    if pratt_attrs.is_empty() {
+        return Ok(()); // do nothing
+    } else if pratt_attrs.len() > 1 {
+        return Err(Error::new(
+            pratt_attrs[1].span(),
+            "expected at most one pratt attribute",
+        ));
+    }
+
+    let attr = pratt_attrs.pop().unwrap();
+    let args = attr.parse_args()?;
+    let PrattArgs { child_rule, pratt } = args;
+
+    if function.sig.inputs.len() < 3 {
+        return Err(Error::new(
+            function.sig.inputs.span(),
+            "A pratt method must have at least 3 arguments",
+        ));
+    }
+
+    // Like for `prec_climb`, the new function only has the middle argument of the original one,
+    // and its extra arguments.
+    let (new_sig, arg_name, extra_args) = operator_method_sig(function)?;
 
     let fn_name = &function.sig.ident;
+    let forward_args = forward_operator_args(&extra_args);
+    let prefix = match helpers.prefix.get(fn_name) {
+        Some(prefix) => quote!(|op, right| {
+            let ___args = &mut *___args.borrow_mut();
+            Self::#prefix(op, right #forward_args)
+        }),
+        None => quote!(|op, _| ::std::result::Result::Err(op.error(format!(
+            "pest_consume::parser: no prefix handler for rule `{}`",
+            op.rule_name(op.as_rule())
+        )))),
+    };
+    let postfix = match helpers.postfix.get(fn_name) {
+        Some(postfix) => quote!(|left, op| {
+            let ___args = &mut *___args.borrow_mut();
+            Self::#postfix(left, op #forward_args)
+        }),
+        None => quote!(|_, op| ::std::result::Result::Err(op.error(format!(
+            "pest_consume::parser: no postfix handler for rule `{}`",
+            op.rule_name(op.as_rule())
+        )))),
+    };
+    let parse = quote!(
+        let ___args = ::std::cell::RefCell::new((#(#extra_args,)*));
+        #arg_name
+            .into_children()
+            .pratt(
+                pratt,
+                |node| {
+                    let ___args = &mut *___args.borrow_mut();
+                    Self::#child_rule(node #forward_args)
+                },
+                #prefix,
+                |left, op, right| {
+                    let ___args = &mut *___args.borrow_mut();
+                    #fn_name(left, op, right #forward_args)
+                },
+                #postfix,
+            )
+    );
+    let parse = match pratt {
+        PrecClimber::Expr(pratt) => quote!(
+            let pratt = &*#pratt;
+            #parse
+        ),
+        PrecClimber::Table(levels) => {
+            let pp = quote!(::pest_consume::pest::pratt_parser);
+            let levels = levels.iter().map(|level| {
+                let ops = level.rules.iter().map(|rule| match level.fixity {
+                    Fixity::Left => quote!(
+                        #pp::Op::infix(#rule_enum::#rule, #pp::Assoc::Left)
+                    ),
+                    Fixity::Right => quote!(
+                        #pp::Op::infix(#rule_enum::#rule, #pp::Assoc::Right)
+                    ),
+                    Fixity::Prefix => {
+                        quote!(#pp::Op::prefix(#rule_enum::#rule))
+                    }
+                    Fixity::Postfix => {
+                        quote!(#pp::Op::postfix(#rule_enum::#rule))
+                    }
+                });
+                quote!(.op(#(#ops)|*))
+            });
+            // Build the parser only once per thread, like the `prec_climb` table.
+            quote!(
+                fn ___with_pratt<T>(
+                    f: impl FnOnce(&#pp::PrattParser<#rule_enum>) -> T,
+                ) -> T {
+                    thread_local! {
+                        static PRATT: #pp::PrattParser<#rule_enum> =
+                            #pp::PrattParser::new() #(#levels)*;
+                    }
+                    PRATT.with(f)
+                }
+                ___with_pratt(|pratt| { #parse })
+            )
+        }
+    };
     *function = parse_quote!(
         #new_sig {
             #function
 
-            #arg_name
-                .into_children()
-                .prec_climb(
-                    &*#climber,
-                    Self::#child_rule,
-                    #fn_name,
-                )
+            #parse
         }
     );
 
     Ok(())
 }
 
-fn apply_special_attrs(f: &mut ParsedFn, rule_enum: &Path) -> Result<()> {
+/// Collects the identifiers and lifetimes (as `'a`) that appear in `tokens`.
+fn collect_names(tokens: TokenStream, names: &mut HashSet<String>) {
+    let mut is_lifetime = false;
+    for token in tokens {
+        match &token {
+            TokenTree::Ident(ident) if is_lifetime => {
+                names.insert(format!("'{}", ident));
+            }
+            TokenTree::Ident(ident) => {
+                names.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => collect_names(group.stream(), names),
+            _ => {}
+        }
+        is_lifetime = match &token {
+            TokenTree::Punct(p) => p.as_char() == '\'',
+            _ => false,
+        };
+    }
+}
+
+fn names_in(tokens: TokenStream) -> HashSet<String> {
+    let mut names = HashSet::new();
+    collect_names(tokens, &mut names);
+    names
+}
+
+fn generic_param_name(param: &GenericParam) -> String {
+    match param {
+        GenericParam::Type(param) => param.ident.to_string(),
+        GenericParam::Lifetime(param) => param.lifetime.to_string(),
+        GenericParam::Const(param) => param.ident.to_string(),
+    }
+}
+
+/// The `Parser` trait can only be implemented with the generic parameters of the impl block that
+/// appear in the parser type. The other ones, e.g. the type of the user data in
+/// `impl<D: Context> CSVParser`, are moved to the methods that use them, with the bounds that
+/// mention them.
+fn move_impl_generics_to_methods(imp: &mut ItemImpl) {
+    let self_ty = &imp.self_ty;
+    let self_names = names_in(quote!(#self_ty));
+    let (kept, moved): (Vec<_>, Vec<_>) = imp
+        .generics
+        .params
+        .iter()
+        .cloned()
+        .partition(|param| self_names.contains(&generic_param_name(param)));
+    if moved.is_empty() {
+        return;
+    }
+    let moved_names: HashSet<String> =
+        moved.iter().map(generic_param_name).collect();
+    let predicates: Vec<WherePredicate> = imp
+        .generics
+        .where_clause
+        .take()
+        .map(|where_clause| where_clause.predicates.into_iter().collect())
+        .unwrap_or_default();
+    let (moved_predicates, kept_predicates): (Vec<_>, Vec<_>) =
+        predicates.into_iter().partition(|predicate| {
+            !names_in(quote!(#predicate)).is_disjoint(&moved_names)
+        });
+    imp.generics.params = kept.into_iter().collect();
+    if !kept_predicates.is_empty() {
+        imp.generics.make_where_clause().predicates =
+            kept_predicates.into_iter().collect();
+    }
+
+    for item in &mut imp.items {
+        let method = match item {
+            ImplItem::Method(method) => method,
+            _ => continue,
+        };
+        // Only add the parameters the method uses, so that they can be inferred at call sites.
+        // A bound may in turn require other parameters.
+        let mut used: HashSet<String> = names_in(quote!(#method))
+            .intersection(&moved_names)
+            .cloned()
+            .collect();
+        let mut is_bound = vec![false; moved_predicates.len()];
+        loop {
+            let mut changed = false;
+            for (i, predicate) in moved_predicates.iter().enumerate() {
+                let names = names_in(quote!(#predicate));
+                if !is_bound[i] && !names.is_disjoint(&used) {
+                    used.extend(names.intersection(&moved_names).cloned());
+                    is_bound[i] = true;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        let bounds: Vec<_> = moved_predicates
+            .iter()
+            .zip(&is_bound)
+            .filter(|(_, is_bound)| **is_bound)
+            .map(|(predicate, _)| predicate.clone())
+            .collect();
+        if used.is_empty() {
+            continue;
+        }
+
+        // Lifetimes have to come first.
+        let generics = &mut method.sig.generics;
+        let params = moved
+            .iter()
+            .filter(|param| used.contains(&generic_param_name(param)));
+        let (lifetimes, others): (Vec<_>, Vec<_>) = params
+            .cloned()
+            .chain(std::mem::take(&mut generics.params))
+            .partition(|param| matches!(param, GenericParam::Lifetime(_)));
+        generics.params = lifetimes.into_iter().chain(others).collect();
+        if !bounds.is_empty() {
+            generics.make_where_clause().predicates.extend(bounds);
+        }
+    }
+}
+
+/// Whether the method was declared without a body, as in `fn number(input: Node) -> Result<f64>;`.
+fn has_no_body(function: &ImplItemMethod) -> bool {
+    match function.block.stmts.as_slice() {
+        [Stmt::Item(Item::Verbatim(tokens))] => tokens.to_string() == ";",
+        _ => false,
+    }
+}
+
+/// Generates the body of the methods declared with `#[from_str]`, `#[as_str]` or `#[from_node]`.
+fn apply_leaf_attrs(f: &mut ParsedFn) -> Result<()> {
+    let function = &mut *f.function;
+    let input_arg = &f.input_arg;
+    let mut attrs = function.attrs.partition_filter(|attr| {
+        attr.path.is_ident("from_str")
+            || attr.path.is_ident("as_str")
+            || attr.path.is_ident("from_node")
+    });
+    if attrs.len() > 1 {
+        return Err(Error::new(
+            attrs[1].span(),
+            "expected at most one `from_str`, `as_str` or `from_node` attribute",
+        ));
+    }
+
+    match (attrs.pop(), has_no_body(function)) {
+        (None, false) => {}
+        (None, true) => {
+            return Err(Error::new(
+                function.sig.ident.span(),
+                "a rule method without a body needs a `#[from_str]`, `#[as_str]` or `#[from_node]` attribute",
+            ))
+        }
+        (Some(attr), false) => {
+            return Err(Error::new(
+                attr.span(),
+                "this attribute generates the body of the method, which must be omitted",
+            ))
+        }
+        (Some(attr), true) if attr.path.is_ident("from_str") => {
+            function.block = parse_quote!({
+                #input_arg
+                    .as_str()
+                    .parse()
+                    .map_err(|e| #input_arg.error(e))
+            });
+        }
+        (Some(attr), true) if attr.path.is_ident("from_node") => {
+            function.block = parse_quote!({
+                ::pest_consume::FromNode::from_node(#input_arg)
+            });
+        }
+        (Some(_), true) => {
+            function.block = parse_quote!({
+                ::std::result::Result::Ok(#input_arg.as_str().into())
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Makes the methods declared with `#[memoize]` reuse their result on nodes with the same rule and
+/// span.
+fn apply_memoize_attr(f: &mut ParsedFn) -> Result<()> {
+    let function = &mut *f.function;
+    let input_arg = &f.input_arg;
+    let attrs = function
+        .attrs
+        .partition_filter(|attr| attr.path.is_ident("memoize"));
+    let attr = match attrs.as_slice() {
+        [] => return Ok(()),
+        [attr] => attr,
+        [_, attr, ..] => {
+            return Err(Error::new(
+                attr.span(),
+                "expected at most one `memoize` attribute",
+            ))
+        }
+    };
+    if !f.extra_args.is_empty() {
+        return Err(Error::new(
+            attr.span(),
+            "`memoize` is not supported on methods with extra arguments",
+        ));
+    }
+    let block = &function.block;
+    function.block = parse_quote!({
+        #input_arg.memoize(|#input_arg| #block)
+    });
+    Ok(())
+}
+
+/// Returns an `EOI` method that accepts the end of the input, unless there is one already. It
+/// doesn't mention `Rule::EOI`, which only exists if the grammar uses `EOI`.
+fn eoi_method(
+    imp: &ItemImpl,
+    rule_enum: &Path,
+    error: &TokenStream,
+    stateful: bool,
+) -> Option<ImplItem> {
+    let has_eoi = imp.items.iter().any(|item| match item {
+        ImplItem::Method(m) => m.sig.ident == "EOI",
+        _ => false,
+    });
+    if has_eoi {
+        return None;
+    }
+    let receiver = if stateful { quote!(&self,) } else { quote!() };
+    Some(parse_quote!(
+        #[allow(non_snake_case)]
+        fn EOI<___D>(
+            #receiver
+            _input: ::pest_consume::Node<'_, #rule_enum, ___D, #error>,
+        ) -> ::std::result::Result<(), #error> {
+            ::std::result::Result::Ok(())
+        }
+    ))
+}
+
+/// Rejects the attributes that generate code calling methods without `self`, and `memoize`, since
+/// the result of a method could depend on the state of the parser.
+fn check_stateful_attrs(function: &ImplItemMethod) -> Result<()> {
+    for attr in &function.attrs {
+        for name in &["prec_climb", "pratt", "memoize"] {
+            if attr.path.is_ident(name) {
+                return Err(Error::new(
+                    attr.span(),
+                    format!("`{}` is not supported in stateful parsers", name),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn apply_special_attrs(
+    f: &mut ParsedFn,
+    rule_enum: &Path,
+    stateful: bool,
+) -> Result<()> {
     let function = &mut *f.function;
     let fn_name = &f.fn_name;
     let input_arg = &f.input_arg;
+    let callee = if stateful {
+        quote!(self.)
+    } else {
+        quote!(Self::)
+    };
+    let extra_args = &f.extra_args;
+    let extra_args = quote!(#(, #extra_args)*);
 
     // `alias` attr
     // f.alias_srcs has always at least 1 element because it has an entry pointing from itself.
@@ -310,13 +1116,13 @@ fn apply_special_attrs(f: &mut ParsedFn, rule_enum: &Path) -> Result<()> {
         }
 
         match #input_arg.as_rule() {
-            #(#rule_enum::#aliases => Self::#aliases(#input_arg),)*
+            #(#rule_enum::#aliases => #callee #aliases(#input_arg #extra_args),)*
             #rule_enum::#fn_name => #block,
-            r => panic!(
-                "pest_consume::parser: called the `{}` method on a node with rule `{:?}`",
+            r => return ::std::result::Result::Err(#input_arg.error(format!(
+                "pest_consume::parser: called the `{}` method on a node with rule `{}`",
                 stringify!(#fn_name),
-                r
-            )
+                #input_arg.rule_name(r)
+            ))),
         }
     });
 
@@ -330,24 +1136,73 @@ pub fn make_parser(
     let attrs: MakeParserAttrs = syn::parse(attrs)?;
     let parser = &attrs.parser;
     let rule_enum = &attrs.rule_enum;
+    let error = match &attrs.error {
+        Some(error) => quote!(#error),
+        None => quote!(::pest_consume::Error<#rule_enum>),
+    };
     let mut imp: ItemImpl = syn::parse(input)?;
+    move_impl_generics_to_methods(&mut imp);
+    let callee = if attrs.stateful {
+        quote!(self.)
+    } else {
+        quote!(Self::)
+    };
+    let eoi = eoi_method(&imp, rule_enum, &error, attrs.stateful);
+    let (helpers, pratt_helpers) = collect_pratt_helpers(&mut imp)?;
+
+    let grammar = match &attrs.grammar {
+        Some(lit) => Some(Grammar::load(lit)?),
+        None => None,
+    };
+    if let (Some(grammar), Some(lit), Some(_)) =
+        (&grammar, &attrs.grammar, &attrs.exhaustive)
+    {
+        check_exhaustive(&imp, &helpers, grammar, lit)?;
+    }
+    // Make cargo rebuild when the grammar changes.
+    let include_grammar = grammar.as_ref().map(|grammar| {
+        let path = grammar.path.to_string_lossy();
+        quote!(
+            const _: &str = include_str!(#path);
+        )
+    });
 
-    let mut alias_map = collect_aliases(&mut imp)?;
+    let display_names = collect_display_names(&mut imp, &helpers)?;
+    let mut alias_map = collect_aliases(&mut imp, &helpers)?;
+    let eoi_ident = Ident::new("EOI", Span::call_site());
+    let eoi = eoi.filter(|_| !alias_map.contains_key(&eoi_ident));
     let rule_alias_branches: Vec<_> = alias_map
         .iter()
         .flat_map(|(tgt, srcs)| iter::repeat(tgt).zip(srcs))
         .map(|(tgt, src)| {
             let ident = &src.ident;
             quote!(
-                #rule_enum::#ident => Self::AliasedRule::#tgt,
+                #rule_enum::#ident => ::std::option::Option::Some(Self::AliasedRule::#tgt),
             )
         })
         .collect();
-    let aliased_rule_variants: Vec<_> =
-        alias_map.iter().map(|(tgt, _)| tgt.clone()).collect();
-    let shortcut_branches: Vec<_> = alias_map
+    let aliased_rule_variants: Vec<_> = alias_map.keys().cloned().collect();
+    let display_name_branches: Vec<_> = display_names
         .iter()
-        .flat_map(|(_tgt, srcs)| srcs)
+        .map(|(rule, name)| {
+            quote!(
+                #rule_enum::#rule => ::std::option::Option::Some(#name),
+            )
+        })
+        .collect();
+    // A method gives its display name to the aliased rule of the same name, if any.
+    let aliased_display_name_branches: Vec<_> = display_names
+        .iter()
+        .filter(|(rule, _)| alias_map.contains_key(rule))
+        .map(|(rule, name)| {
+            quote!(
+                Self::AliasedRule::#rule => ::std::option::Option::Some(#name),
+            )
+        })
+        .collect();
+    let shortcut_branches: Vec<_> = alias_map
+        .values()
+        .flatten()
         .map(|AliasSrc { ident, is_shortcut }| {
             quote!(
                 #rule_enum::#ident => #is_shortcut,
@@ -355,21 +1210,21 @@ pub fn make_parser(
         })
         .collect();
 
-    let fn_map: HashMap<Ident, ParsedFn> = imp
-        .items
-        .iter_mut()
-        .flat_map(|item| match item {
-            ImplItem::Method(m) => Some(m),
-            _ => None,
-        })
+    let fn_map: HashMap<Ident, ParsedFn> = rule_methods(&mut imp, &helpers)
         .map(|method| {
             *method = parse_quote!(
                 #[allow(non_snake_case)]
                 #method
             );
-            apply_prec_climb_attr(method)?;
-            let mut f = parse_fn(method, &mut alias_map)?;
-            apply_special_attrs(&mut f, &rule_enum)?;
+            if attrs.stateful {
+                check_stateful_attrs(method)?;
+            }
+            apply_prec_climb_attr(method, rule_enum)?;
+            apply_pratt_attr(method, rule_enum, &pratt_helpers)?;
+            let mut f = parse_fn(method, &mut alias_map, attrs.stateful)?;
+            apply_leaf_attrs(&mut f)?;
+            apply_memoize_attr(&mut f)?;
+            apply_special_attrs(&mut f, rule_enum, attrs.stateful)?;
             Ok((f.fn_name.clone(), f))
         })
         .collect::<Result<_>>()?;
@@ -382,25 +1237,40 @@ pub fn make_parser(
             // essentially the same signature anyways.
             let f = fn_map.get(&srcs.first().unwrap().ident).unwrap();
             let input_arg = f.input_arg.clone();
+            let extra_args = &f.extra_args;
+            let extra_args = quote!(#(, #extra_args)*);
             let mut sig = f.function.sig.clone();
             sig.ident = tgt.clone();
             let srcs = srcs.iter().map(|src| &src.ident);
+            // `tgt` might be an arbitrary identifier, so we can only match on `#rule_enum::#tgt`
+            // if the grammar says it is a rule.
+            let is_rule = grammar.as_ref().map_or(false, |grammar| {
+                grammar
+                    .non_silent_rules()
+                    .iter()
+                    .any(|rule| *rule != "EOI" && tgt == rule)
+            });
+            let missing_method = if is_rule {
+                quote!(
+                    r @ #rule_enum::#tgt =>
+                        return ::std::result::Result::Err(#input_arg.error(format!(
+                            "pest_consume::parser: missing method for rule `{}`",
+                            #input_arg.rule_name(r),
+                        ))),
+                )
+            } else {
+                quote!()
+            };
 
             Ok(parse_quote!(
                 #sig {
                     match #input_arg.as_rule() {
-                        #(#rule_enum::#srcs => Self::#srcs(#input_arg),)*
-                        // We can't match on #rule_enum::#tgt since `tgt` might be an arbitrary
-                        // identifier.
-                        r if &format!("{:?}", r) == stringify!(#tgt) =>
-                            return ::std::result::Result::Err(#input_arg.error(format!(
-                                "pest_consume::parser: missing method for rule {}",
-                                stringify!(#tgt),
-                            ))),
+                        #(#rule_enum::#srcs => #callee #srcs(#input_arg #extra_args),)*
+                        #missing_method
                         r => return ::std::result::Result::Err(#input_arg.error(format!(
-                            "pest_consume::parser: called method `{}` on a node with rule `{:?}`",
+                            "pest_consume::parser: called method `{}` on a node with rule `{}`",
                             stringify!(#tgt),
-                            r
+                            #input_arg.rule_name(r)
                         ))),
                     }
                 }
@@ -408,10 +1278,13 @@ pub fn make_parser(
         })
         .collect::<Result<_>>()?;
     imp.items.extend(extra_fns);
+    imp.items.extend(eoi);
 
     let ty = &imp.self_ty;
     let (impl_generics, _, where_clause) = imp.generics.split_for_impl();
     Ok(quote!(
+        #include_grammar
+
         #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
         #[allow(non_camel_case_types)]
         pub enum AliasedRule {
@@ -422,11 +1295,11 @@ pub fn make_parser(
             type Rule = #rule_enum;
             type AliasedRule = AliasedRule;
             type Parser = #parser;
-            fn rule_alias(rule: Self::Rule) -> Self::AliasedRule {
+            type Error = #error;
+            fn rule_alias(rule: Self::Rule) -> ::std::option::Option<Self::AliasedRule> {
                 match rule {
                     #(#rule_alias_branches)*
-                    // TODO: return a proper error ?
-                    r => panic!("Rule `{:?}` does not have a corresponding parsing method", r),
+                    _ => ::std::option::Option::None,
                 }
             }
             fn allows_shortcut(rule: Self::Rule) -> bool {
@@ -435,6 +1308,20 @@ pub fn make_parser(
                     _ => false,
                 }
             }
+            fn rule_display_name(rule: Self::Rule) -> ::std::option::Option<&'static str> {
+                match rule {
+                    #(#display_name_branches)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+            fn aliased_rule_display_name(
+                rule: Self::AliasedRule,
+            ) -> ::std::option::Option<&'static str> {
+                match rule {
+                    #(#aliased_display_name_branches)*
+                    _ => ::std::option::Option::None,
+                }
+            }
         }
 
         #imp
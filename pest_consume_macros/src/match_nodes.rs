@@ -8,32 +8,64 @@ use syn::{
     Token, Type,
 };
 
+mod kw {
+    syn::custom_keyword!(lazy);
+    syn::custom_keyword!(recover);
+    syn::custom_keyword!(with);
+}
+
 #[derive(Clone)]
 struct MatchBranch {
-    // Patterns all have the form [a, b, c.., d], with a bunch of simple patterns,
-    // optionally a multiple pattern, and then some more simple patterns.
-    singles_before_multiple: Vec<(Option<Ident>, Pat)>,
-    multiple: Option<(Option<Ident>, Pat)>,
-    singles_after_multiple: Vec<(Option<Ident>, Pat)>,
-
+    // Patterns have the form [a, b?, c.., d], i.e. a list of items each matching one, at most
+    // one, or any number of nodes.
+    pattern: Vec<MatchBranchPatternItem>,
+    // `where $expr`: evaluated on the raw nodes, before anything is parsed.
+    raw_guard: Option<Expr>,
+    // `if $expr`: evaluated on the parsed values.
+    guard: Option<Expr>,
     body: Expr,
 }
 
 #[derive(Clone)]
-enum MatchBranchPatternItem {
-    Single {
-        rule_name: Option<Ident>,
-        binder: Pat,
-    },
-    Multiple {
-        rule_name: Option<Ident>,
-        binder: Pat,
-        slice_token: Token![..],
-    },
+struct MatchBranchPatternItem {
+    // The rules this item accepts, as in `a(x) | b(x)`. Empty if the item accepts any node.
+    rule_names: Vec<Ident>,
+    binder: Binder,
+    // `a(x with y, z)`: extra arguments passed to the method after the node.
+    args: Vec<Expr>,
+    multiplicity: Multiplicity,
+    // Only relevant for `..` items.
+    mode: MultipleMode,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MultipleMode {
+    // `..`: the nodes are parsed upfront, and the first error is returned.
+    Eager,
+    // `..lazy`: the nodes are parsed on demand.
+    Lazy,
+    // `..recover`: the nodes are parsed upfront, and failing ones are reported and skipped.
+    Recover,
+}
+
+#[derive(Clone)]
+enum Binder {
+    Pat(Pat),
+    // `[a, b..]`: matches the children of the node against a sub-pattern.
+    Nested(Vec<MatchBranchPatternItem>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Multiplicity {
+    Single,
+    Optional,
+    Multiple,
 }
 
 #[derive(Clone)]
 struct MacroInput {
+    // `self` or another value to call the methods on, for stateful parsers.
+    receiver: Option<Expr>,
     parser: Type,
     input_expr: Expr,
     branches: Punctuated<MatchBranch, Token![,]>,
@@ -41,104 +73,248 @@ struct MacroInput {
 
 impl Parse for MatchBranch {
     fn parse(input: ParseStream) -> Result<Self> {
-        let contents;
-        let _: token::Bracket = bracketed!(contents in input);
-
-        let pattern: Punctuated<MatchBranchPatternItem, Token![,]> =
-            Punctuated::parse_terminated(&contents)?;
-        use MatchBranchPatternItem::{Multiple, Single};
-        let mut singles_before_multiple = Vec::new();
-        let mut multiple = None;
-        let mut singles_after_multiple = Vec::new();
-        for item in pattern.clone() {
-            match item {
-                Single { rule_name, binder } => {
-                    if multiple.is_none() {
-                        singles_before_multiple.push((rule_name, binder))
-                    } else {
-                        singles_after_multiple.push((rule_name, binder))
-                    }
-                }
-                Multiple {
-                    rule_name,
-                    binder,
-                    slice_token,
-                } => {
-                    if multiple.is_none() {
-                        multiple = Some((rule_name, binder))
-                    } else {
-                        return Err(Error::new(
-                            slice_token.span(),
-                            "multiple variable-length patterns are not allowed",
-                        ));
-                    }
-                }
-            }
-        }
+        let pattern = parse_pattern(input)?;
+
+        let raw_guard = if input.peek(Token![where]) {
+            let _: Token![where] = input.parse()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        let guard = if input.peek(Token![if]) {
+            let _: Token![if] = input.parse()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
 
         let _: Token![=>] = input.parse()?;
         let body = input.parse()?;
 
         Ok(MatchBranch {
-            singles_before_multiple,
-            multiple,
-            singles_after_multiple,
+            pattern,
+            raw_guard,
+            guard,
             body,
         })
     }
 }
 
+/// Parses a pattern of the form `[a, b?, c.., d]`.
+fn parse_pattern(input: ParseStream) -> Result<Vec<MatchBranchPatternItem>> {
+    let contents;
+    let _: token::Bracket = bracketed!(contents in input);
+
+    let pattern: Punctuated<MatchBranchPatternItem, Token![,]> =
+        Punctuated::parse_terminated(&contents)?;
+    let pattern: Vec<_> = pattern.into_iter().collect();
+    check_ambiguity(&pattern)?;
+    Ok(pattern)
+}
+
+impl Parse for Binder {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(token::Bracket) {
+            Ok(Binder::Nested(parse_pattern(input)?))
+        } else {
+            Ok(Binder::Pat(input.parse()?))
+        }
+    }
+}
+
+impl Binder {
+    fn span(&self) -> Span {
+        match self {
+            Binder::Pat(pat) => pat.span(),
+            Binder::Nested(pattern) => pattern
+                .first()
+                .map(|item| item.binder.span())
+                .unwrap_or_else(Span::call_site),
+        }
+    }
+}
+
+impl MatchBranchPatternItem {
+    /// Whether this item can match any node that `next` can match, in which case greedily
+    /// matching with this item would starve `next`.
+    fn shadows(&self, next: &Self) -> bool {
+        if self.rule_names.is_empty() {
+            true
+        } else {
+            self.rule_names.iter().any(|r| next.rule_names.contains(r))
+        }
+    }
+}
+
+/// Variable-length and optional items are split greedily, which is only unsurprising if such an
+/// item doesn't match the rules of the neighbouring variable-length and optional items that
+/// follow it. E.g. in `[x.., y..]` it would be unclear which nodes `y` should match, whereas
+/// `[a(x).., y..]` is fine.
+fn check_ambiguity(pattern: &[MatchBranchPatternItem]) -> Result<()> {
+    for (i, item) in pattern.iter().enumerate() {
+        if item.multiplicity == Multiplicity::Single {
+            continue;
+        }
+        // Items that can match a variable number of nodes and are not separated by a single item.
+        let neighbours = pattern[i + 1..]
+            .iter()
+            .take_while(|next| next.multiplicity != Multiplicity::Single);
+        for next in neighbours {
+            let is_ambiguous = (item.multiplicity == Multiplicity::Multiple
+                || next.multiplicity == Multiplicity::Multiple)
+                && item.shadows(next);
+            if is_ambiguous {
+                return Err(Error::new(
+                    next.binder.span(),
+                    "ambiguous pattern: this item can match the same nodes as a previous \
+                     variable-length or optional item",
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
 impl Parse for MatchBranchPatternItem {
     fn parse(input: ParseStream) -> Result<Self> {
         let ahead = input.fork();
         let _: TokenTree = ahead.parse()?;
-        if ahead.peek(token::Paren) {
+        let (rule_names, RuleBinder { binder, args }) = if ahead
+            .peek(token::Paren)
+        {
             // If `input` starts with `foo(`
-            let contents;
-            let rule_name = input.parse()?;
-            parenthesized!(contents in input);
-            if input.peek(Token![..]) {
-                let binder = contents.parse()?;
-                let slice_token = input.parse()?;
-                Ok(MatchBranchPatternItem::Multiple {
-                    rule_name: Some(rule_name),
-                    binder,
-                    slice_token,
-                })
-            } else if input.is_empty() || input.peek(Token![,]) {
-                let binder = contents.parse()?;
-                Ok(MatchBranchPatternItem::Single {
-                    rule_name: Some(rule_name),
-                    binder,
-                })
-            } else {
-                Err(input.error("expected `..` or nothing"))
+            let (rule_name, binder) = parse_rule_and_binder(input)?;
+            let mut rule_names = vec![rule_name];
+            // Alternatives, as in `foo(x) | bar(x)`
+            while input.peek(Token![|]) {
+                let _: Token![|] = input.parse()?;
+                let (rule_name, other_binder) = parse_rule_and_binder(input)?;
+                if other_binder.to_string() != binder.to_string() {
+                    return Err(Error::new_spanned(
+                        other_binder,
+                        "all the alternatives of an item must use the same binder",
+                    ));
+                }
+                rule_names.push(rule_name);
             }
+            (rule_names, syn::parse2(binder)?)
         } else {
-            // A single pattern captures the node itself without parsing anything.
-            let binder = input.parse()?;
-            if input.peek(Token![..]) {
-                let slice_token = input.parse()?;
-                Ok(MatchBranchPatternItem::Multiple {
-                    rule_name: None,
-                    binder,
-                    slice_token,
-                })
-            } else if input.is_empty() || input.peek(Token![,]) {
-                Ok(MatchBranchPatternItem::Single {
-                    rule_name: None,
-                    binder,
-                })
-            } else {
-                Err(input.error("expected `..` or nothing"))
+            // A pattern without a rule captures the node itself without parsing anything. We
+            // stop before any `..` so that `x..lazy` isn't parsed as a range pattern.
+            let mut binder = TokenStream::new();
+            while !(input.is_empty()
+                || input.peek(Token![,])
+                || input.peek(Token![..])
+                || input.peek(Token![?]))
+            {
+                binder.extend(Some(input.parse::<TokenTree>()?));
             }
-        }
+            let binder = RuleBinder {
+                binder: syn::parse2(binder)?,
+                args: Vec::new(),
+            };
+            (Vec::new(), binder)
+        };
+
+        let mut mode = MultipleMode::Eager;
+        let multiplicity = if input.peek(Token![..]) {
+            let _: Token![..] = input.parse()?;
+            if input.peek(kw::lazy) {
+                let kw: kw::lazy = input.parse()?;
+                if let Binder::Nested(_) = binder {
+                    return Err(Error::new(
+                        kw.span,
+                        "`..lazy` is not supported on nested patterns",
+                    ));
+                }
+                mode = MultipleMode::Lazy;
+            } else if input.peek(kw::recover) {
+                let kw: kw::recover = input.parse()?;
+                if rule_names.is_empty() {
+                    return Err(Error::new(
+                        kw.span,
+                        "`..recover` requires a rule, since raw nodes can't fail to parse",
+                    ));
+                }
+                if let Binder::Nested(_) = binder {
+                    return Err(Error::new(
+                        kw.span,
+                        "`..recover` is not supported on nested patterns",
+                    ));
+                }
+                mode = MultipleMode::Recover;
+            }
+            Multiplicity::Multiple
+        } else if input.peek(Token![?]) {
+            let _: Token![?] = input.parse()?;
+            Multiplicity::Optional
+        } else if input.is_empty() || input.peek(Token![,]) {
+            Multiplicity::Single
+        } else {
+            return Err(input.error(
+                "expected `..`, `..lazy`, `..recover`, `?` or nothing",
+            ));
+        };
+
+        Ok(MatchBranchPatternItem {
+            rule_names,
+            binder,
+            args,
+            multiplicity,
+            mode,
+        })
     }
 }
 
+/// The contents of the parentheses in `a(...)`.
+struct RuleBinder {
+    binder: Binder,
+    args: Vec<Expr>,
+}
+
+impl Parse for RuleBinder {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let binder = input.parse()?;
+        let args = if input.peek(kw::with) {
+            let kw: kw::with = input.parse()?;
+            if let Binder::Nested(_) = binder {
+                return Err(Error::new(
+                    kw.span,
+                    "nested patterns don't call a method, so they can't take arguments",
+                ));
+            }
+            let args: Punctuated<Expr, Token![,]> =
+                Punctuated::parse_separated_nonempty(input)?;
+            args.into_iter().collect()
+        } else {
+            Vec::new()
+        };
+        Ok(RuleBinder { binder, args })
+    }
+}
+
+fn parse_rule_and_binder(input: ParseStream) -> Result<(Ident, TokenStream)> {
+    let contents;
+    let rule_name = input.parse()?;
+    parenthesized!(contents in input);
+    Ok((rule_name, contents.parse()?))
+}
+
 impl Parse for MacroInput {
     fn parse(input: ParseStream) -> Result<Self> {
-        let parser = if input.peek(token::Lt) {
+        // The first expression is the receiver if it isn't directly followed by the branches.
+        let mut receiver = None;
+        let mut input_expr = None;
+        if !input.peek(token::Lt) {
+            let expr = input.parse()?;
+            let _: Token![;] = input.parse()?;
+            if input.is_empty() || input.peek(token::Bracket) {
+                input_expr = Some(expr);
+            } else {
+                receiver = Some(expr);
+            }
+        }
+        let parser = if input_expr.is_none() && input.peek(token::Lt) {
             let _: token::Lt = input.parse()?;
             let parser = input.parse()?;
             let _: token::Gt = input.parse()?;
@@ -147,11 +323,18 @@ impl Parse for MacroInput {
         } else {
             parse_quote!(Self)
         };
-        let input_expr = input.parse()?;
-        let _: Token![;] = input.parse()?;
+        let input_expr = match input_expr {
+            Some(input_expr) => input_expr,
+            None => {
+                let input_expr = input.parse()?;
+                let _: Token![;] = input.parse()?;
+                input_expr
+            }
+        };
         let branches = Punctuated::parse_terminated(input)?;
 
         Ok(MacroInput {
+            receiver,
             parser,
             input_expr,
             branches,
@@ -159,96 +342,558 @@ impl Parse for MacroInput {
     }
 }
 
-fn make_branch(
-    branch: &MatchBranch,
+/// Whether the node `node`, whose aliased rule is `aliased`, matches `rule_name`. `EOI` compares
+/// the rule itself, since the `EOI` method that the parser macro generates has no aliased rule.
+fn rule_matches(
+    rule_name: &Ident,
+    node: &TokenStream,
+    aliased: &TokenStream,
+    parser: &Type,
+) -> TokenStream {
+    if rule_name == "EOI" {
+        quote!(#node.as_rule() == <#parser as ::pest_consume::Parser>::Rule::EOI)
+    } else {
+        quote!(
+            #aliased == ::std::option::Option::Some(
+                <#parser as ::pest_consume::Parser>::AliasedRule::#rule_name
+            )
+        )
+    }
+}
+
+/// Generates an expression that finds how the nodes in `i_nodes` split among the items of
+/// `pattern`, as a `Result<[usize; N], usize>`. `i_node_rules` must contain the aliased rules of
+/// the nodes.
+fn make_match(
+    pattern: &[MatchBranchPatternItem],
     i_nodes: &Ident,
     i_node_rules: &Ident,
     parser: &Type,
-) -> Result<TokenStream> {
-    let aliased_rule = quote!(<#parser as ::pest_consume::Parser>::AliasedRule);
+) -> TokenStream {
+    let rule = quote!(<#parser as ::pest_consume::Parser>::Rule);
+    let i_node_list = Ident::new("___node_list", Span::call_site());
+    let mut needs_node_list = false;
 
-    // Find which branch to take
-    let mut conditions = Vec::new();
-    let start = branch.singles_before_multiple.len();
-    let end = branch.singles_after_multiple.len();
-    conditions.push(quote!(
-        #start + #end <= #i_node_rules.len()
-    ));
-    let matches_rule = |rule: &Option<_>, x| match rule {
-        Some(rule_name) => quote!(#x == #aliased_rule::#rule_name),
-        None => quote!(true),
-    };
-    for (i, (rule, _)) in branch.singles_before_multiple.iter().enumerate() {
-        conditions.push(matches_rule(rule, quote!(#i_node_rules[#i])))
-    }
-    for (i, (rule, _)) in branch.singles_after_multiple.iter().enumerate() {
-        conditions.push(matches_rule(
-            rule,
-            quote!(#i_node_rules[#i_node_rules.len()-1 - #i]),
-        ))
-    }
-    if let Some((rule, _)) = &branch.multiple {
-        // Hygiene looks dodgy for the `r`, but it works.
-        let matches_r = matches_rule(rule, quote!(*r));
-        conditions.push(quote!(
-            {
-                // We can't use .all() directly in the pattern guard; see
-                // https://github.com/rust-lang/rust/issues/59803.
-                let all_match = |slice: &[_]| {
-                    slice.iter().all(|r| #matches_r)
+    // For each item, a predicate that checks whether the node at a given index can be matched by
+    // this item.
+    let items: Vec<_> = pattern.iter().map(|item| {
+        let multiplicity = match item.multiplicity {
+            Multiplicity::Single => quote!(Single),
+            Multiplicity::Optional => quote!(Optional),
+            Multiplicity::Multiple => quote!(Multiple),
+        };
+        let rule_names = &item.rule_names;
+        // Hygiene looks dodgy for the `i`, but it works.
+        let matches = match &item.binder {
+            Binder::Pat(_) if rule_names.is_empty() => quote!(|_: usize| true),
+            Binder::Pat(_) => {
+                needs_node_list |= rule_names.iter().any(|r| r == "EOI");
+                let node = quote!(#i_node_list[i]);
+                let aliased = quote!(#i_node_rules[i]);
+                let tests = rule_names
+                    .iter()
+                    .map(|r| rule_matches(r, &node, &aliased, parser));
+                quote!(|i: usize| { #(#tests)||* })
+            }
+            // Wrapper rules usually don't have a method, so we look at the rule itself.
+            Binder::Nested(subpattern) => {
+                needs_node_list = true;
+                let rule_matches = if rule_names.is_empty() {
+                    quote!(true)
+                } else {
+                    quote!(#(#i_node_list[i].as_rule() == #rule::#rule_names)||*)
                 };
-                all_match(&#i_node_rules[#start..#i_node_rules.len() - #end])
+                let subpattern_matches =
+                    make_match(subpattern, i_nodes, i_node_rules, parser);
+                quote!(|i: usize| {
+                    (#rule_matches) && {
+                        let #i_nodes = #i_node_list[i].children();
+                        let #i_node_rules: ::std::vec::Vec<_> =
+                            #i_nodes.aliased_rules::<#parser>().collect();
+                        #subpattern_matches.is_ok()
+                    }
+                })
             }
+        };
+        quote!((
+            ::pest_consume::Multiplicity::#multiplicity,
+            &(#matches) as &dyn Fn(usize) -> bool,
         ))
+    }).collect();
+    let matched = quote!(::pest_consume::match_pattern(
+        &[#(#items,)*],
+        #i_node_rules.len(),
+    ));
+
+    if needs_node_list {
+        quote!({
+            let #i_node_list: ::std::vec::Vec<_> = #i_nodes.clone().collect();
+            #matched
+        })
     } else {
-        // No variable-length pattern, so the size must be exactly the number of patterns
-        conditions.push(quote!(
-            #start + #end == #i_node_rules.len()
-        ))
+        matched
     }
+}
 
-    let parse_rule = |rule: &Option<_>, node| match rule {
-        Some(rule_name) => quote!(#parser::#rule_name(#node)),
-        None => quote!(Ok(#node)),
+/// Generates the statements that consume the nodes in `i_nodes` and bind the variables of
+/// `pattern`, given the number of nodes taken by each item in `i_counts`. The methods are called
+/// as `#callee #method(...)`, where `callee` is either `Parser::` or `receiver.`. If
+/// `buffer_errors` is set, the errors of `..recover` items are pushed to `___recovered` instead
+/// of being reported.
+fn make_parses(
+    pattern: &[MatchBranchPatternItem],
+    i_nodes: &Ident,
+    i_counts: &Ident,
+    parser: &Type,
+    callee: &TokenStream,
+    buffer_errors: bool,
+) -> Vec<TokenStream> {
+    // Parses a node with the method of its rule, among the rules accepted by an item.
+    let consume = |item: &MatchBranchPatternItem, node| {
+        let rule_names = &item.rule_names;
+        let args = &item.args;
+        let args = quote!(#(, #args)*);
+        match rule_names.as_slice() {
+            [rule_name] => quote!(#callee #rule_name(#node #args)),
+            _ => {
+                let i_node = Ident::new("___node", Span::call_site());
+                let tests = rule_names.iter().map(|r| {
+                    rule_matches(r, &quote!(#i_node), &quote!(___rule), parser)
+                });
+                quote!({
+                    let #i_node = #node;
+                    let ___rule = #i_node.as_aliased_rule::<#parser>();
+                    #(
+                        if #tests {
+                            #callee #rule_names(#i_node #args)
+                        } else
+                    )* {
+                        unreachable!()
+                    }
+                })
+            }
+        }
+    };
+    let parse_rule = |item: &MatchBranchPatternItem, node| {
+        if item.rule_names.is_empty() {
+            quote!(#node)
+        } else {
+            let consume = consume(item, node);
+            quote!(#consume?)
+        }
     };
-    // Once we have found a branch that matches, we need to parse the nodes.
+    // Matches a sub-pattern against the children of a node, and returns the tuple of the
+    // variables it binds.
+    let parse_subpattern = |subpattern: &[MatchBranchPatternItem], node| {
+        let i_node_rules = Ident::new("___node_rules", Span::call_site());
+        let matched = make_match(subpattern, i_nodes, &i_node_rules, parser);
+        let parses = make_parses(
+            subpattern,
+            i_nodes,
+            i_counts,
+            parser,
+            callee,
+            buffer_errors,
+        );
+        let idents = subpattern_bindings(subpattern)
+            .into_iter()
+            .map(|(_, ident)| ident);
+        quote!({
+            #[allow(unused_mut)]
+            let mut #i_nodes = #node.into_children();
+            let #i_node_rules: ::std::vec::Vec<_> =
+                #i_nodes.aliased_rules::<#parser>().collect();
+            let #i_counts = #matched.unwrap();
+            #(#parses)*
+            (#(#idents,)*)
+        })
+    };
+
     let mut parses = Vec::new();
-    for (rule_name, binder) in branch.singles_before_multiple.iter() {
+    for (i, item) in pattern.iter().enumerate() {
         let next_node = quote!(#i_nodes.next().unwrap());
-        let parse = parse_rule(rule_name, next_node);
-        parses.push(quote!(
-            let #binder = #parse?;
-        ))
-    }
-    // Note the `rev()`: we are taking nodes from the end of the iterator in reverse order, so that
-    // only the unmatched nodes are left in the iterator for the variable-length pattern, if any.
-    for (rule, binder) in branch.singles_after_multiple.iter().rev() {
-        let next_node = quote!(#i_nodes.next_back().unwrap());
-        let parse = parse_rule(rule, next_node);
-        parses.push(quote!(
-            let #binder = #parse?;
-        ))
-    }
-    // Once we've taken nodes from the front and back, what remains are the nodes matched by the
-    // `..` pattern.
-    if let Some((rule, binder)) = &branch.multiple {
-        // Hygiene looks dodgy for `n`, but it works.
-        let parse_n = parse_rule(rule, quote!(n));
-        parses.push(quote!(
-            let #binder = #i_nodes
-                .map(|n| #parse_n)
-                .collect::<::std::result::Result<::std::vec::Vec<_>, _>>()?
-                .into_iter();
-        ))
+        let parse = match &item.binder {
+            Binder::Pat(binder) => {
+                let parse = match item.multiplicity {
+                    Multiplicity::Single => parse_rule(item, next_node),
+                    Multiplicity::Optional => {
+                        let parse = parse_rule(item, next_node);
+                        quote!(
+                            if #i_counts[#i] == 1 {
+                                ::std::option::Option::Some(#parse)
+                            } else {
+                                ::std::option::Option::None
+                            }
+                        )
+                    }
+                    // The iterator parses a copy of the nodes, and we skip past them in the
+                    // original.
+                    Multiplicity::Multiple
+                        if item.mode == MultipleMode::Lazy =>
+                    {
+                        let nodes =
+                            quote!(#i_nodes.clone().take(#i_counts[#i]));
+                        let nodes = if item.rule_names.is_empty() {
+                            nodes
+                        } else {
+                            let consume = consume(item, quote!(n));
+                            quote!(#nodes.map(|n| #consume))
+                        };
+                        quote!({
+                            let ___iter = #nodes;
+                            (&mut #i_nodes).take(#i_counts[#i]).for_each(drop);
+                            ___iter
+                        })
+                    }
+                    // Errors go to the error sink of the nodes, which returns them if errors are
+                    // not being collected.
+                    Multiplicity::Multiple
+                        if item.mode == MultipleMode::Recover =>
+                    {
+                        let consume = consume(item, next_node);
+                        let report = if buffer_errors {
+                            quote!(___recovered.push(e))
+                        } else {
+                            quote!(#i_nodes.report_error(e)?)
+                        };
+                        quote!({
+                            let mut ___values = ::std::vec::Vec::new();
+                            for _ in 0..#i_counts[#i] {
+                                match #consume {
+                                    ::std::result::Result::Ok(value) => ___values.push(value),
+                                    ::std::result::Result::Err(e) => #report,
+                                }
+                            }
+                            ___values.into_iter()
+                        })
+                    }
+                    Multiplicity::Multiple => {
+                        // Hygiene looks dodgy for `n`, but it works.
+                        let nodes = quote!((&mut #i_nodes).take(#i_counts[#i]));
+                        if item.rule_names.is_empty() {
+                            quote!(#nodes.collect::<::std::vec::Vec<_>>().into_iter())
+                        } else {
+                            let consume = consume(item, quote!(n));
+                            quote!(
+                                #nodes
+                                    .map(|n| #consume)
+                                    .collect::<::std::result::Result<::std::vec::Vec<_>, _>>()?
+                                    .into_iter()
+                            )
+                        }
+                    }
+                };
+                quote!(let #binder = #parse;)
+            }
+            // The variables of a sub-pattern are bound like the result of a method would be:
+            // as-is, as `Option`s, or as iterators.
+            Binder::Nested(subpattern) => {
+                let bound = subpattern_bindings(subpattern);
+                let binders = bound
+                    .iter()
+                    .map(|(mutability, ident)| quote!(#mutability #ident));
+                let idents: Vec<_> =
+                    bound.iter().map(|(_, ident)| ident).collect();
+                let parse = match item.multiplicity {
+                    Multiplicity::Single => {
+                        parse_subpattern(subpattern, next_node)
+                    }
+                    Multiplicity::Optional => {
+                        let parse = parse_subpattern(subpattern, next_node);
+                        quote!(
+                            if #i_counts[#i] == 1 {
+                                let (#(#idents,)*) = #parse;
+                                (#(::std::option::Option::Some(#idents),)*)
+                            } else {
+                                (#({
+                                    let _ = stringify!(#idents);
+                                    ::std::option::Option::None
+                                },)*)
+                            }
+                        )
+                    }
+                    Multiplicity::Multiple => {
+                        let parse = parse_subpattern(subpattern, quote!(n));
+                        let indices = (0..idents.len()).map(syn::Index::from);
+                        let indices2 = indices.clone();
+                        let i_values =
+                            Ident::new("___values", Span::call_site());
+                        let i_value = Ident::new("___value", Span::call_site());
+                        quote!({
+                            let mut #i_values = (#({
+                                let _ = stringify!(#idents);
+                                ::std::vec::Vec::new()
+                            },)*);
+                            for n in (&mut #i_nodes).take(#i_counts[#i]) {
+                                let #i_value = #parse;
+                                #(#i_values.#indices.push(#i_value.#indices);)*
+                            }
+                            (#(#i_values.#indices2.into_iter(),)*)
+                        })
+                    }
+                };
+                quote!(let (#(#binders,)*) = #parse;)
+            }
+        };
+        parses.push(parse);
     }
+    parses
+}
+
+fn make_branch(
+    branch: &MatchBranch,
+    i_nodes: &Ident,
+    i_node_rules: &Ident,
+    parser: &Type,
+    callee: &TokenStream,
+) -> Result<TokenStream> {
+    let i_counts = Ident::new("___counts", Span::call_site());
+
+    // Find which branch to take. If it doesn't match, we record how far it went for the error
+    // message.
+    let matched = make_match(&branch.pattern, i_nodes, i_node_rules, parser);
+    let matched = quote!(
+        match #matched {
+            ::std::result::Result::Ok(#i_counts) => ::std::option::Option::Some(#i_counts),
+            ::std::result::Result::Err(furthest) => {
+                ___furthest = ___furthest.max(furthest);
+                ::std::option::Option::None
+            }
+        }
+    );
+
+    // When a guard rejects a branch, all the nodes matched, so the error is about all of them.
+    let reject = quote!(
+        ___furthest = ___furthest.max(#i_node_rules.len());
+        ___guard_rejected = true;
+    );
+
+    // A `where` guard only sees the nodes, so it can be checked before parsing anything. We bind
+    // the nodes each item would match, without consuming them.
+    let matched = match &branch.raw_guard {
+        None => matched,
+        Some(raw_guard) => {
+            let i_raw_nodes = Ident::new("___raw_nodes", Span::call_site());
+            let bindings =
+                branch.pattern.iter().enumerate().map(|(i, item)| {
+                    let binder = match &item.binder {
+                        Binder::Pat(Pat::Ident(pat))
+                            if pat.subpat.is_none() =>
+                        {
+                            quote!(#pat)
+                        }
+                        _ => quote!(_),
+                    };
+                    let next_node = quote!(#i_raw_nodes.next().unwrap());
+                    let nodes = match item.multiplicity {
+                        Multiplicity::Single => next_node,
+                        Multiplicity::Optional => quote!(
+                            if #i_counts[#i] == 1 {
+                                ::std::option::Option::Some(#next_node)
+                            } else {
+                                ::std::option::Option::None
+                            }
+                        ),
+                        Multiplicity::Multiple => quote!(
+                            (&mut #i_raw_nodes)
+                                .take(#i_counts[#i])
+                                .collect::<::std::vec::Vec<_>>()
+                        ),
+                    };
+                    quote!(
+                        #[allow(unused_variables, unused_mut)]
+                        let #binder = #nodes;
+                    )
+                });
+            quote!(
+                match #matched {
+                    ::std::option::Option::Some(#i_counts) if {
+                        #[allow(unused_mut)]
+                        let mut #i_raw_nodes = #i_nodes.clone();
+                        #(#bindings)*
+                        #raw_guard
+                    } => ::std::option::Option::Some(#i_counts),
+                    ::std::option::Option::Some(_) => {
+                        #reject
+                        ::std::option::Option::None
+                    }
+                    ::std::option::Option::None => ::std::option::Option::None,
+                }
+            )
+        }
+    };
+
+    // Once we have found a branch that matches, we need to parse the nodes, in order.
+    // With an `if` guard, errors of `..recover` items are only reported if the guard holds.
+    let parses = make_parses(
+        &branch.pattern,
+        i_nodes,
+        &i_counts,
+        parser,
+        callee,
+        branch.guard.is_some(),
+    );
 
     let body = &branch.body;
-    Ok(quote!(
-        _ if #(#conditions &&)* true => {
-            #(#parses)*
-            #body
+    Ok(match &branch.guard {
+        // Variables used in the guard count as used.
+        None if branch.raw_guard.is_some() => quote!(
+            if let ::std::option::Option::Some(#i_counts) = #matched {
+                #(#[allow(unused_variables)] #parses)*
+                #body
+            }
+        ),
+        None => quote!(
+            if let ::std::option::Option::Some(#i_counts) = #matched {
+                #(#parses)*
+                #body
+            }
+        ),
+        // An `if` guard needs the parsed values, so we parse from a copy of the nodes and only
+        // hand over the bound variables if the guard holds. Otherwise the next branch gets to
+        // try the untouched nodes.
+        Some(guard) => {
+            let bound = subpattern_bindings(&branch.pattern);
+            let idents = bound.iter().map(|(_, ident)| ident);
+            let bound = bound
+                .iter()
+                .map(|(mutability, ident)| quote!(#mutability #ident));
+            let i_bound = Ident::new("___bound", Span::call_site());
+            quote!(
+                if let ::std::option::Option::Some(#i_bound) = match #matched {
+                    ::std::option::Option::Some(#i_counts) => {
+                        #[allow(unused_mut)]
+                        let mut #i_nodes = #i_nodes.clone();
+                        #[allow(unused_mut)]
+                        let mut ___recovered = ::std::vec::Vec::new();
+                        #(#[allow(unused_mut)] #parses)*
+                        if #guard {
+                            ::std::option::Option::Some((___recovered, (#(#idents,)*)))
+                        } else {
+                            #reject
+                            ::std::option::Option::None
+                        }
+                    }
+                    _ => ::std::option::Option::None,
+                } {
+                    let (___recovered, #i_bound) = #i_bound;
+                    for e in ___recovered {
+                        #i_nodes.report_error(e)?;
+                    }
+                    // Variables used in the guard count as used.
+                    #[allow(unused_variables)]
+                    let (#(#bound,)*) = #i_bound;
+                    #body
+                }
+            )
+        }
+    })
+}
+
+/// Collects the variables bound by a pattern of `match_nodes!`, with their mutability.
+fn subpattern_bindings(
+    pattern: &[MatchBranchPatternItem],
+) -> Vec<(Option<Token![mut]>, Ident)> {
+    let mut out = Vec::new();
+    for item in pattern {
+        match &item.binder {
+            Binder::Pat(pat) => collect_bindings(pat, &mut out),
+            Binder::Nested(subpattern) => {
+                out.extend(subpattern_bindings(subpattern))
+            }
+        }
+    }
+    out
+}
+
+/// Collects the variables bound by a pattern, with their mutability.
+fn collect_bindings(pat: &Pat, out: &mut Vec<(Option<Token![mut]>, Ident)>) {
+    match pat {
+        Pat::Ident(pat) => {
+            out.push((pat.mutability, pat.ident.clone()));
+            if let Some((_, subpat)) = &pat.subpat {
+                collect_bindings(subpat, out);
+            }
         }
-    ))
+        Pat::Box(pat) => collect_bindings(&pat.pat, out),
+        Pat::Reference(pat) => collect_bindings(&pat.pat, out),
+        Pat::Type(pat) => collect_bindings(&pat.pat, out),
+        // All the cases of an or-pattern bind the same variables.
+        Pat::Or(pat) => {
+            if let Some(case) = pat.cases.first() {
+                collect_bindings(case, out);
+            }
+        }
+        Pat::Slice(pat) => {
+            pat.elems.iter().for_each(|pat| collect_bindings(pat, out))
+        }
+        Pat::Tuple(pat) => {
+            pat.elems.iter().for_each(|pat| collect_bindings(pat, out))
+        }
+        Pat::TupleStruct(pat) => pat
+            .pat
+            .elems
+            .iter()
+            .for_each(|pat| collect_bindings(pat, out)),
+        Pat::Struct(pat) => pat
+            .fields
+            .iter()
+            .for_each(|field| collect_bindings(&field.pat, out)),
+        _ => {}
+    }
+}
+
+/// Renders the shape of a pattern for error messages, e.g. `[record.., EOI]`. Returns a format
+/// string, with a `{}` for each rule name, and the expressions that give the display names of
+/// those rules.
+fn render_pattern(
+    pattern: &[MatchBranchPatternItem],
+    parser: &Type,
+) -> (String, Vec<TokenStream>) {
+    let aliased_rule = quote!(<#parser as ::pest_consume::Parser>::AliasedRule);
+    let rule = quote!(<#parser as ::pest_consume::Parser>::Rule);
+    let mut args = Vec::new();
+    let items: Vec<_> = pattern
+        .iter()
+        .map(|item| {
+            // Nested patterns and `EOI` match on the rule itself rather than the aliased one.
+            for rule_name in &item.rule_names {
+                args.push(match item.binder {
+                    Binder::Pat(_) if rule_name != "EOI" => quote!(
+                        ___aliased_rule_name(#aliased_rule::#rule_name)
+                    ),
+                    _ => quote!(___rule_name(#rule::#rule_name)),
+                });
+            }
+            let rules = vec!["{}"; item.rule_names.len()].join(" | ");
+            let item_str = match (&item.binder, rules.is_empty()) {
+                (Binder::Pat(_), true) => "_".to_owned(),
+                (Binder::Pat(_), false) => rules,
+                (Binder::Nested(subpattern), is_untyped) => {
+                    let (sub_str, sub_args) =
+                        render_pattern(subpattern, parser);
+                    args.extend(sub_args);
+                    if is_untyped {
+                        sub_str
+                    } else {
+                        format!("{}({})", rules, sub_str)
+                    }
+                }
+            };
+            let suffix = match item.multiplicity {
+                Multiplicity::Single => "",
+                Multiplicity::Optional => "?",
+                Multiplicity::Multiple => "..",
+            };
+            // `a | b..` would be ambiguous.
+            if item.rule_names.len() > 1 && !suffix.is_empty() {
+                format!("({}){}", item_str, suffix)
+            } else {
+                format!("{}{}", item_str, suffix)
+            }
+        })
+        .collect();
+    (format!("[{}]", items.join(", ")), args)
 }
 
 pub fn match_nodes(
@@ -261,23 +906,124 @@ pub fn match_nodes(
 
     let input_expr = &input.input_expr;
     let parser = &input.parser;
+    let callee = match &input.receiver {
+        Some(receiver @ Expr::Path(_)) => quote!(#receiver.),
+        Some(receiver) => quote!((#receiver).),
+        None => quote!(#parser::),
+    };
     let branches = input
         .branches
         .iter()
-        .map(|br| make_branch(br, &i_nodes, &i_node_rules, parser))
+        .map(|br| make_branch(br, &i_nodes, &i_node_rules, parser, &callee))
         .collect::<Result<Vec<_>>>()?;
 
+    // A node whose rule has no method can only be matched by an untyped pattern or a nested one,
+    // so otherwise it is most likely the culprit.
+    let rule = quote!(<#parser as ::pest_consume::Parser>::Rule);
+    let nested_items = input
+        .branches
+        .iter()
+        .flat_map(|branch| &branch.pattern)
+        .filter(|item| matches!(item.binder, Binder::Nested(_)));
+    let mut nested_rules = Vec::new();
+    let mut untyped_nested = false;
+    for item in nested_items {
+        untyped_nested |= item.rule_names.is_empty();
+        nested_rules.extend(&item.rule_names);
+    }
+    let check_missing_method = if untyped_nested {
+        quote!()
+    } else {
+        quote!(
+            let ___nested_rules: &[#rule] = &[#(#rule::#nested_rules),*];
+            if let ::std::option::Option::Some(i) = #i_node_rules
+                .iter()
+                .zip(#i_nodes.clone())
+                .position(|(rule, node)| {
+                    // The generated `EOI` method has no aliased rule. This only runs when no
+                    // branch matched, so the cost of formatting doesn't matter.
+                    rule.is_none()
+                        && !___nested_rules.contains(&node.as_rule())
+                        && format!("{:?}", node.as_rule()) != "EOI"
+                })
+            {
+                let node = #i_nodes.nth(i).unwrap();
+                return ::std::result::Result::Err(node.error(format!(
+                    "Rule `{}` does not have a corresponding parsing method",
+                    node.rule_name(node.as_rule()),
+                )));
+            }
+        )
+    };
+
+    // The rule names are only known at runtime, since they can have a display name.
+    let mut patterns: Vec<String> = Vec::new();
+    let mut pattern_args: Vec<TokenStream> = Vec::new();
+    let mut seen: Vec<String> = Vec::new();
+    for branch in &input.branches {
+        let (pattern, args) = render_pattern(&branch.pattern, parser);
+        let key = format!("{} {}", pattern, quote!(#(#args)*));
+        if !seen.contains(&key) {
+            seen.push(key);
+            patterns.push(format!("`{}`", pattern));
+            pattern_args.extend(args);
+        }
+    }
+    let expected = match patterns.as_slice() {
+        [pattern] => format!("expected {}", pattern),
+        _ => format!("expected one of {}", patterns.join(", ")),
+    };
+
     Ok(quote!({
         #[allow(unused_mut)]
         let mut #i_nodes = #input_expr;
         let #i_node_rules: ::std::vec::Vec<_> = #i_nodes.aliased_rules::<#parser>().collect();
+        // The index of the first node that no branch could match.
+        let mut ___furthest = 0;
+        // Whether a branch matched but its guard didn't hold.
+        #[allow(unused_mut)]
+        let mut ___guard_rejected = false;
 
-        #[allow(unreachable_code, clippy::int_plus_one)]
-        match () {
-            #(#branches,)*
-            _ => return ::std::result::Result::Err(#i_nodes.error(
-                format!("Nodes didn't match any pattern: {:?}", #i_node_rules)
-            )),
+        #[allow(unreachable_code)]
+        {
+            #(#branches else)* {
+                #check_missing_method
+                let ___rule_name = |rule: <#parser as ::pest_consume::Parser>::Rule| {
+                    match <#parser as ::pest_consume::Parser>::rule_display_name(rule) {
+                        ::std::option::Option::Some(name) => name.to_owned(),
+                        ::std::option::Option::None => format!("{:?}", rule),
+                    }
+                };
+                let ___aliased_rule_name =
+                        |rule: <#parser as ::pest_consume::Parser>::AliasedRule| {
+                    match <#parser as ::pest_consume::Parser>::aliased_rule_display_name(rule) {
+                        ::std::option::Option::Some(name) => name.to_owned(),
+                        ::std::option::Option::None => format!("{:?}", rule),
+                    }
+                };
+                // Nodes without a method are shown with their own rule.
+                let found: ::std::vec::Vec<_> = #i_node_rules
+                    .into_iter()
+                    .zip(#i_nodes.clone())
+                    .map(|(rule, node)| match rule {
+                        ::std::option::Option::Some(rule) => ___aliased_rule_name(rule),
+                        ::std::option::Option::None => ___rule_name(node.as_rule()),
+                    })
+                    .collect();
+                let mut message = format!(
+                    concat!(#expected, ", found `[{}]`"),
+                    #(#pattern_args,)*
+                    found.join(", "),
+                );
+                if ___guard_rejected {
+                    message.push_str(", which a guard rejected");
+                }
+                // Point at the node where all the branches failed, if any.
+                return ::std::result::Result::Err(match #i_nodes.nth(___furthest) {
+                    ::std::option::Option::Some(node) => node.error(message),
+                    ::std::option::Option::None => #i_nodes.error(message),
+                })
+            }
         }
     }))
 }